@@ -0,0 +1,33 @@
+#![no_main]
+
+// Throws arbitrary input at every function that parses untrusted tokens.
+// None of them may panic or hang, whatever the input; malformed tokens
+// must come back as a `TokenError`.
+//
+// Run with `cargo +nightly fuzz run decode` from the repository root.
+
+use crabtoken::{Claims, Validation};
+use libfuzzer_sys::fuzz_target;
+
+const SECRET: &str = "a fuzzing secret of at least 32 bytes";
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(token) = std::str::from_utf8(data) else {
+        return;
+    };
+    let validation = Validation {
+        allow_legacy: true,
+        required_claims: vec!["sub".to_string()],
+        ..Validation::default()
+    };
+
+    let _ = crabtoken::is_well_formed(token);
+    let _ = crabtoken::parse_token(token);
+    let _ = crabtoken::introspect(token);
+    let _ = crabtoken::decode_token::<Claims>(token);
+    let _ = crabtoken::decode_token::<serde_json::Value>(token);
+    let _ = crabtoken::decode_legacy_token::<Claims>(token);
+    let _ = crabtoken::verify_token::<Claims>(SECRET, token);
+    let _ = crabtoken::verify::<Claims>(SECRET, token, &validation);
+    let _ = crabtoken::verify_token_raw(SECRET, token);
+});
@@ -0,0 +1,30 @@
+use alloc::vec::Vec;
+use serde::{Serialize, Serializer};
+use serde_json::Value;
+
+// A payload serialized canonically, for `TokenOptions::canonical`.
+//
+// Map keys are written sorted by their UTF-8 bytes, whatever order the
+// map was built in, and nested values are canonicalized the same way.
+// Integers go through `u64` or `i64`, so every serializer sees the same
+// value however narrow the field was, and MessagePack writes each with
+// its shortest encoding.
+pub(crate) struct Canonical<'a>(pub(crate) &'a Value);
+
+impl Serialize for Canonical<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self.0 {
+            Value::Array(items) => serializer.collect_seq(items.iter().map(Canonical)),
+            Value::Object(map) => {
+                let mut entries: Vec<_> = map.iter().collect();
+                entries.sort_unstable_by_key(|(key, _)| *key);
+                let entries = entries.into_iter().map(|(key, value)| (key, Canonical(value)));
+                serializer.collect_map(entries)
+            }
+            value => value.serialize(serializer),
+        }
+    }
+}
@@ -0,0 +1,370 @@
+#[cfg(feature = "std")]
+use crate::RandomIds;
+use crate::{Expirable, IdSource, SetExpiry, TokenError, TokenErrorKind};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::Deref;
+
+// A payload with the standard registered claims and a map of custom claims.
+//
+// `Claims` saves defining a payload struct for the common case. Every
+// registered claim except `exp` is optional and left out of the token when
+// unset. Custom claims are stored alongside the registered ones.
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(not(feature = "redact-claims"), derive(Debug))]
+pub struct Claims {
+    // The issuer of the token.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iss: Option<String>,
+    // The subject of the token, usually a user id.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sub: Option<String>,
+    // The intended audience of the token.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aud: Option<Audience>,
+    // The expiration timestamp (Unix timestamp).
+    pub exp: i64,
+    // The "not before" timestamp (Unix timestamp).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nbf: Option<i64>,
+    // The issued-at timestamp (Unix timestamp).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iat: Option<i64>,
+    // A unique identifier for the token.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jti: Option<String>,
+    // The version the token was issued at, checked against
+    // `Validation::min_version`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<u64>,
+    // Any claims that aren't registered claims.
+    #[serde(flatten)]
+    pub custom: BTreeMap<String, Value>,
+}
+
+impl Claims {
+    // Returns a builder for a new set of claims.
+    pub fn builder() -> ClaimsBuilder {
+        ClaimsBuilder::default()
+    }
+}
+
+// Prints the names of the claims that are set, not their values.
+#[cfg(feature = "redact-claims")]
+impl fmt::Debug for Claims {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let registered = [
+            ("iss", self.iss.is_some()),
+            ("sub", self.sub.is_some()),
+            ("aud", self.aud.is_some()),
+            ("exp", true),
+            ("nbf", self.nbf.is_some()),
+            ("iat", self.iat.is_some()),
+            ("jti", self.jti.is_some()),
+            ("version", self.version.is_some()),
+        ];
+        let claims: Vec<&str> = registered
+            .into_iter()
+            .filter_map(|(name, set)| set.then_some(name))
+            .chain(self.custom.keys().map(String::as_str))
+            .collect();
+        f.debug_struct("Claims").field("claims", &claims).finish()
+    }
+}
+
+impl Expirable for Claims {
+    fn exp(&self) -> i64 {
+        self.exp
+    }
+
+    fn nbf(&self) -> Option<i64> {
+        self.nbf
+    }
+
+    fn iat(&self) -> Option<i64> {
+        self.iat
+    }
+
+    fn iss(&self) -> Option<&str> {
+        self.iss.as_deref()
+    }
+
+    fn jti(&self) -> Option<&str> {
+        self.jti.as_deref()
+    }
+
+    fn version(&self) -> Option<u64> {
+        self.version
+    }
+
+    fn aud(&self) -> Option<&str> {
+        match self.aud.as_ref()? {
+            Audience::Single(aud) => Some(aud),
+            Audience::Multiple(auds) => match auds.as_slice() {
+                [aud] => Some(aud),
+                _ => None,
+            },
+        }
+    }
+
+    fn has_aud(&self, aud: &str) -> bool {
+        self.aud.as_ref().is_some_and(|audience| audience.contains(aud))
+    }
+}
+
+impl SetExpiry for Claims {
+    fn set_exp(&mut self, exp: i64) {
+        self.exp = exp;
+    }
+}
+
+// The `aud` claim, which names either a single audience or several.
+//
+// As in RFC 7519, a single audience is encoded as a plain string and
+// several as a list of strings. Both forms are accepted when decoding, but
+// a token with an empty audience or an empty list fails to decode, caused
+// by an `InvalidClaim` error.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged, try_from = "RawAudience")]
+pub enum Audience {
+    // A single audience.
+    Single(String),
+    // Several audiences.
+    Multiple(Vec<String>),
+}
+
+impl Audience {
+    // Returns whether `aud` is one of the audiences.
+    pub fn contains(&self, aud: &str) -> bool {
+        match self {
+            Audience::Single(single) => single == aud,
+            Audience::Multiple(auds) => auds.iter().any(|candidate| candidate == aud),
+        }
+    }
+
+    // Returns an iterator over the audiences.
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        let auds = match self {
+            Audience::Single(single) => core::slice::from_ref(single),
+            Audience::Multiple(auds) => auds.as_slice(),
+        };
+        auds.iter().map(String::as_str)
+    }
+}
+
+impl From<String> for Audience {
+    fn from(aud: String) -> Self {
+        Audience::Single(aud)
+    }
+}
+
+impl From<&str> for Audience {
+    fn from(aud: &str) -> Self {
+        Audience::Single(aud.into())
+    }
+}
+
+impl From<Vec<String>> for Audience {
+    fn from(auds: Vec<String>) -> Self {
+        Audience::Multiple(auds)
+    }
+}
+
+// The `aud` claim as decoded, before it is checked.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawAudience {
+    Single(String),
+    Multiple(Vec<String>),
+}
+
+impl TryFrom<RawAudience> for Audience {
+    type Error = TokenError;
+
+    fn try_from(raw: RawAudience) -> Result<Self, Self::Error> {
+        let valid = match &raw {
+            RawAudience::Single(aud) => !aud.is_empty(),
+            RawAudience::Multiple(auds) => {
+                !auds.is_empty() && auds.iter().all(|aud| !aud.is_empty())
+            }
+        };
+        if !valid {
+            return Err(TokenErrorKind::InvalidClaim("aud".into()).into());
+        }
+        Ok(match raw {
+            RawAudience::Single(aud) => Audience::Single(aud),
+            RawAudience::Multiple(auds) => Audience::Multiple(auds),
+        })
+    }
+}
+
+// The `sub` claim, which is never empty.
+//
+// `Claims` keeps the subject as a plain string. Payload types of your own
+// can use `Subject` instead, so a token with an empty subject fails to
+// decode, caused by an `InvalidClaim` error, rather than reaching the code
+// that uses it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Subject(String);
+
+impl Subject {
+    // Creates a subject, rejecting an empty string with `InvalidClaim`.
+    pub fn new(sub: impl Into<String>) -> Result<Self, TokenError> {
+        let sub = sub.into();
+        if sub.is_empty() {
+            return Err(TokenErrorKind::InvalidClaim("sub".into()).into());
+        }
+        Ok(Subject(sub))
+    }
+
+    // Returns the subject string.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    // Returns the subject string, consuming the subject.
+    pub fn into_string(self) -> String {
+        self.0
+    }
+}
+
+impl TryFrom<String> for Subject {
+    type Error = TokenError;
+
+    fn try_from(sub: String) -> Result<Self, Self::Error> {
+        Subject::new(sub)
+    }
+}
+
+impl TryFrom<&str> for Subject {
+    type Error = TokenError;
+
+    fn try_from(sub: &str) -> Result<Self, Self::Error> {
+        Subject::new(sub)
+    }
+}
+
+impl From<Subject> for String {
+    fn from(sub: Subject) -> Self {
+        sub.0
+    }
+}
+
+impl fmt::Display for Subject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Deref for Subject {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<str> for Subject {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+// A builder for `Claims`.
+#[derive(Debug, Clone, Default)]
+pub struct ClaimsBuilder {
+    claims: Claims,
+}
+
+impl ClaimsBuilder {
+    // Sets the issuer (`iss`) claim.
+    pub fn issuer(mut self, iss: impl Into<String>) -> Self {
+        self.claims.iss = Some(iss.into());
+        self
+    }
+
+    // Sets the subject (`sub`) claim.
+    pub fn subject(mut self, sub: impl Into<String>) -> Self {
+        self.claims.sub = Some(sub.into());
+        self
+    }
+
+    // Sets the audience (`aud`) claim to a single audience.
+    pub fn audience(mut self, aud: impl Into<String>) -> Self {
+        self.claims.aud = Some(Audience::Single(aud.into()));
+        self
+    }
+
+    // Sets the audience (`aud`) claim to several audiences.
+    pub fn audiences<I>(mut self, auds: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        let auds = auds.into_iter().map(Into::into).collect();
+        self.claims.aud = Some(Audience::Multiple(auds));
+        self
+    }
+
+    // Sets the expiration (`exp`) claim to the given Unix timestamp.
+    pub fn expires_at(mut self, exp: i64) -> Self {
+        self.claims.exp = exp;
+        self
+    }
+
+    // Sets the "not before" (`nbf`) claim to the given Unix timestamp.
+    pub fn not_before(mut self, nbf: i64) -> Self {
+        self.claims.nbf = Some(nbf);
+        self
+    }
+
+    // Sets the issued-at (`iat`) claim to the given Unix timestamp.
+    pub fn issued_at(mut self, iat: i64) -> Self {
+        self.claims.iat = Some(iat);
+        self
+    }
+
+    // Sets the token id (`jti`) claim.
+    pub fn jwt_id(mut self, jti: impl Into<String>) -> Self {
+        self.claims.jti = Some(jti.into());
+        self
+    }
+
+    // Sets the token id (`jti`) claim to a new unique 128-bit id.
+    //
+    // Every call generates a different id, so each token can be revoked or
+    // checked for replay on its own. Read it back from `Claims::jti` to
+    // store it.
+    #[cfg(feature = "std")]
+    pub fn unique_jwt_id(self) -> Self {
+        self.jwt_id_from(&mut RandomIds)
+    }
+
+    // Sets the token id (`jti`) claim to the next id from `ids`.
+    pub fn jwt_id_from(mut self, ids: &mut impl IdSource) -> Self {
+        self.claims.jti = Some(ids.next_id());
+        self
+    }
+
+    // Sets the `version` claim, which `Validation::min_version` checks.
+    pub fn version(mut self, version: u64) -> Self {
+        self.claims.version = Some(version);
+        self
+    }
+
+    // Adds a custom claim.
+    pub fn claim(mut self, name: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.claims.custom.insert(name.into(), value.into());
+        self
+    }
+
+    // Returns the built claims.
+    pub fn build(self) -> Claims {
+        self.claims
+    }
+}
@@ -0,0 +1,95 @@
+#[cfg(all(
+    feature = "std",
+    feature = "chrono",
+    not(all(feature = "wasm", target_arch = "wasm32"))
+))]
+use chrono::Utc;
+#[cfg(all(
+    feature = "std",
+    not(feature = "chrono"),
+    not(all(feature = "wasm", target_arch = "wasm32"))
+))]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// A source of the current time used for time-based claim checks.
+//
+// Verification uses `SystemClock` by default. Implement this trait, or use
+// `FixedClock`, to check tokens as of a chosen time, for example to test
+// what happens exactly at a token's expiration.
+pub trait Clock {
+    // Returns the current time (Unix timestamp).
+    fn now(&self) -> i64;
+
+    // Returns the current time (Unix timestamp in milliseconds).
+    //
+    // Used for tokens with millisecond expiry. The default is `now` in
+    // whole seconds.
+    fn now_millis(&self) -> i64 {
+        self.now().saturating_mul(1000)
+    }
+}
+
+// The system clock.
+//
+// The time is read with chrono when the `chrono` feature is enabled, as
+// it is by default, and with `std::time::SystemTime` otherwise. With the
+// `wasm` feature on `wasm32` targets it is read from JavaScript's
+// `Date.now()`, since the std time APIs aren't available in the browser.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+#[cfg(all(feature = "std", feature = "wasm", target_arch = "wasm32"))]
+impl Clock for SystemClock {
+    fn now(&self) -> i64 {
+        self.now_millis().div_euclid(1000)
+    }
+
+    fn now_millis(&self) -> i64 {
+        js_sys::Date::now() as i64
+    }
+}
+
+#[cfg(all(
+    feature = "std",
+    feature = "chrono",
+    not(all(feature = "wasm", target_arch = "wasm32"))
+))]
+impl Clock for SystemClock {
+    fn now(&self) -> i64 {
+        Utc::now().timestamp()
+    }
+
+    fn now_millis(&self) -> i64 {
+        Utc::now().timestamp_millis()
+    }
+}
+
+#[cfg(all(
+    feature = "std",
+    not(feature = "chrono"),
+    not(all(feature = "wasm", target_arch = "wasm32"))
+))]
+impl Clock for SystemClock {
+    fn now(&self) -> i64 {
+        self.now_millis().div_euclid(1000)
+    }
+
+    fn now_millis(&self) -> i64 {
+        match SystemTime::now().duration_since(UNIX_EPOCH) {
+            Ok(elapsed) => i64::try_from(elapsed.as_millis()).unwrap_or(i64::MAX),
+            // The system clock is set before 1970.
+            Err(err) => i64::try_from(err.duration().as_millis()).map_or(i64::MIN, |ms| -ms),
+        }
+    }
+}
+
+// A clock that always returns the same time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FixedClock(pub i64);
+
+impl Clock for FixedClock {
+    fn now(&self) -> i64 {
+        self.0
+    }
+}
@@ -0,0 +1,175 @@
+use crate::{Token, TokenError, TokenErrorKind};
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+// The longest cookie value `to_cookie_value` accepts, in bytes.
+//
+// Browsers allow about 4096 bytes per cookie, counting its name and
+// attributes, so this leaves room for them.
+pub const MAX_COOKIE_VALUE_LEN: usize = 3800;
+
+// Returns the token as a cookie value.
+//
+// Tokens only use the base64 alphabets and `.`, which are all allowed in
+// a cookie value, so the value is the token itself. It is checked against
+// `MAX_COOKIE_VALUE_LEN`, so a token that a browser would silently drop
+// fails here instead. Split longer tokens with `to_cookie_chunks`.
+// Strings with characters a cookie value can't hold, such as spaces or
+// `;`, are rejected.
+//
+// # Arguments
+//
+// * `token` - The token to store in a cookie.
+//
+// # Returns
+//
+// * `Ok(String)` - The cookie value.
+// * `Err(TokenError)` - `TooLarge` if the token is longer than `MAX_COOKIE_VALUE_LEN`, or
+//   `InvalidEncoding` if it has characters a cookie value can't hold.
+pub fn to_cookie_value(token: &str) -> Result<String, TokenError> {
+    check_cookie_safe(token)?;
+    if token.len() > MAX_COOKIE_VALUE_LEN {
+        return Err(TokenErrorKind::TooLarge.into());
+    }
+    Ok(token.into())
+}
+
+// Reads a token back from a cookie value written by `to_cookie_value`.
+//
+// The value may be wrapped in double quotes, as RFC 6265 allows. Only the
+// shape of the token is checked, as in `Token::from_str`; verify it as
+// usual.
+//
+// # Arguments
+//
+// * `value` - The cookie value.
+//
+// # Returns
+//
+// * `Ok(Token)` - The token stored in the cookie.
+// * `Err(TokenError)` - `InvalidFormat` if the value is not a token.
+pub fn from_cookie_value(value: &str) -> Result<Token, TokenError> {
+    unquote(value).parse()
+}
+
+// Splits a token across several cookies of at most `max_len` bytes each.
+//
+// The chunks are named `{name}.0`, `{name}.1` and so on, in order, so a
+// token in a `session` cookie is stored as `session.0`, `session.1`, ...
+// Each name is returned with its value, to be set as separate cookies.
+// When a shorter token replaces a longer one, expire the chunks it no
+// longer uses, or `from_cookie_chunks` will reassemble them too and the
+// token will fail to verify.
+//
+// # Arguments
+//
+// * `name` - The name the chunk names are built from.
+// * `token` - The token to split.
+// * `max_len` - The longest value of a chunk, in bytes, at least 1.
+//
+// # Returns
+//
+// * `Ok(Vec<(String, String)>)` - The name and value of each chunk, in order.
+// * `Err(TokenError)` - `InvalidEncoding` if the token has characters a cookie value can't
+//   hold.
+pub fn to_cookie_chunks(
+    name: &str,
+    token: &str,
+    max_len: usize,
+) -> Result<Vec<(String, String)>, TokenError> {
+    check_cookie_safe(token)?;
+    let max_len = max_len.max(1);
+    // The token is ASCII now, so any byte offset is a character boundary.
+    let chunks = (0..token.len())
+        .step_by(max_len)
+        .enumerate()
+        .map(|(index, start)| {
+            let end = token.len().min(start + max_len);
+            (format!("{}.{}", name, index), String::from(&token[start..end]))
+        })
+        .collect();
+    Ok(chunks)
+}
+
+// Reassembles a token split by `to_cookie_chunks`.
+//
+// `cookies` can be every cookie of the request: only those named
+// `{name}.{index}` are used, joined in index order. The indices must run
+// from 0 without gaps, each given once and written as `to_cookie_chunks`
+// writes it, without leading zeros, so `{name}.00` can't stand in for
+// `{name}.0`. A missing last chunk can't be told from a shorter token, and
+// fails when the token is verified.
+//
+// # Arguments
+//
+// * `name` - The name the chunk names were built from.
+// * `cookies` - The names and values of the request's cookies.
+//
+// # Returns
+//
+// * `Ok(Token)` - The reassembled token. Verify it as usual.
+// * `Err(TokenError)` - `InvalidFormat` if a chunk is missing, repeated or misnumbered, or the
+//   chunks don't form a token.
+pub fn from_cookie_chunks<'a, I>(name: &str, cookies: I) -> Result<Token, TokenError>
+where
+    I: IntoIterator<Item = (&'a str, &'a str)>,
+{
+    let mut chunks = BTreeMap::new();
+    for (cookie_name, value) in cookies {
+        let index = cookie_name
+            .strip_prefix(name)
+            .and_then(|rest| rest.strip_prefix('.'))
+            .filter(|index| !index.is_empty() && index.bytes().all(|byte| byte.is_ascii_digit()));
+        if let Some(index) = index {
+            if chunks.insert(chunk_index(index)?, unquote(value)).is_some() {
+                return Err(TokenErrorKind::InvalidFormat.into());
+            }
+        }
+    }
+
+    let mut token = String::new();
+    for (expected, (index, value)) in chunks.into_iter().enumerate() {
+        if index != expected {
+            return Err(TokenErrorKind::InvalidFormat.into());
+        }
+        token.push_str(value);
+    }
+    token.parse()
+}
+
+// Rejects strings with characters outside the `cookie-octet` set of
+// RFC 6265: controls, whitespace, `"`, `,`, `;`, `\` and non-ASCII. None
+// of them are base64 either, so the error names the segment holding the
+// first one.
+fn check_cookie_safe(token: &str) -> Result<(), TokenError> {
+    let is_cookie_octet = |byte: &u8| {
+        matches!(byte, 0x21 | 0x23..=0x2B | 0x2D..=0x3A | 0x3C..=0x5B | 0x5D..=0x7E)
+    };
+    if let Some(pos) = token.bytes().position(|byte| !is_cookie_octet(&byte)) {
+        let segment = match token.as_bytes()[..pos].iter().filter(|&&byte| byte == b'.').count() {
+            0 => "header",
+            1 => "payload",
+            _ => "signature",
+        };
+        return Err(TokenErrorKind::InvalidEncoding(segment).into());
+    }
+    Ok(())
+}
+
+// Parses the decimal index of a chunk, rejecting leading zeros.
+fn chunk_index(index: &str) -> Result<usize, TokenError> {
+    if index.len() > 1 && index.starts_with('0') {
+        return Err(TokenErrorKind::InvalidFormat.into());
+    }
+    index.parse().map_err(|_| TokenErrorKind::InvalidFormat.into())
+}
+
+// Strips the double quotes a cookie value may be wrapped in.
+fn unquote(value: &str) -> &str {
+    value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .unwrap_or(value)
+}
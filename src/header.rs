@@ -0,0 +1,272 @@
+use crate::{Format, Header, TokenError, TokenErrorKind, TOKEN_VERSION};
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use rmp::{encode, Marker};
+
+// Reads and writes the header segment, a MessagePack map of a few short
+// fields.
+//
+// Headers are handled with the low-level `rmp` crate rather than
+// `rmp_serde`, which needs the standard library, so tokens can be read and
+// created on `no_std` targets without the `msgpack` feature. The bytes are
+// the same as `rmp_serde::to_vec_named` writes for a `Header`.
+
+// A header as read from a token, with the algorithm kept as its name so
+// an unknown algorithm is reported as such rather than as malformed data.
+pub(crate) struct RawHeader {
+    pub(crate) ver: u8,
+    pub(crate) alg: String,
+    pub(crate) fmt: Format,
+    pub(crate) kid: Option<String>,
+    pub(crate) typ: Option<String>,
+}
+
+// Serializes a header as a MessagePack map keyed by field name. Fields
+// that are `None` are left out.
+pub(crate) fn write(header: &Header) -> Vec<u8> {
+    let fields = 3 + u32::from(header.kid.is_some()) + u32::from(header.typ.is_some());
+    let mut bytes = Vec::new();
+    // Writing to a `Vec` can't fail, so the results are ignored.
+    let _ = encode::write_map_len(&mut bytes, fields);
+    let _ = encode::write_str(&mut bytes, "ver");
+    let _ = encode::write_uint(&mut bytes, u64::from(header.ver));
+    let _ = encode::write_str(&mut bytes, "alg");
+    let _ = encode::write_str(&mut bytes, header.alg.name());
+    let _ = encode::write_str(&mut bytes, "fmt");
+    let _ = encode::write_str(&mut bytes, format_name(header.fmt));
+    for (name, value) in [("kid", &header.kid), ("typ", &header.typ)] {
+        if let Some(value) = value {
+            let _ = encode::write_str(&mut bytes, name);
+            let _ = encode::write_str(&mut bytes, value);
+        }
+    }
+    bytes
+}
+
+// Deserializes a header, allowing at most `max_depth` nested arrays and
+// maps, the header itself included.
+//
+// As with a derived `Deserialize`, unknown fields are skipped, a missing
+// `ver` or `fmt` gets its default, and `kid` and `typ` may be nil.
+pub(crate) fn read(bytes: &[u8], max_depth: usize) -> Result<RawHeader, TokenError> {
+    let mut reader = Reader(bytes);
+    let fields = match reader.value(max_depth, false)? {
+        Value::Map(fields) => fields,
+        _ => return Err(invalid()),
+    };
+
+    let mut ver = None;
+    let mut alg = None;
+    let mut fmt = None;
+    let mut kid = None;
+    let mut typ = None;
+    for _ in 0..fields {
+        let Value::Str(name) = reader.value(max_depth - 1, true)? else {
+            return Err(invalid());
+        };
+        let value = reader.value(max_depth - 1, true)?;
+        let first = match (name, value) {
+            ("ver", Value::Uint(value)) => {
+                set(&mut ver, u8::try_from(value).map_err(|_| invalid())?)
+            }
+            ("alg", Value::Str(value)) => set(&mut alg, value.to_string()),
+            ("fmt", Value::Str(value)) => set(&mut fmt, parse_format(value)?),
+            ("kid", value) => set(&mut kid, optional_str(value)?),
+            ("typ", value) => set(&mut typ, optional_str(value)?),
+            ("ver" | "alg" | "fmt", _) => return Err(invalid()),
+            _ => true,
+        };
+        if !first {
+            return Err(invalid());
+        }
+    }
+
+    Ok(RawHeader {
+        ver: ver.unwrap_or(TOKEN_VERSION),
+        alg: alg.ok_or_else(invalid)?,
+        fmt: fmt.unwrap_or_default(),
+        kid: kid.flatten(),
+        typ: typ.flatten(),
+    })
+}
+
+// Returns the name a format is recorded under, as in its serde renames.
+fn format_name(format: Format) -> &'static str {
+    match format {
+        Format::MessagePack => "msgpack",
+        Format::Json => "json",
+        Format::Raw => "raw",
+    }
+}
+
+fn parse_format(name: &str) -> Result<Format, TokenError> {
+    match name {
+        "msgpack" => Ok(Format::MessagePack),
+        "json" => Ok(Format::Json),
+        "raw" => Ok(Format::Raw),
+        _ => Err(invalid()),
+    }
+}
+
+fn optional_str(value: Value<'_>) -> Result<Option<String>, TokenError> {
+    match value {
+        Value::Nil => Ok(None),
+        Value::Str(value) => Ok(Some(value.to_string())),
+        _ => Err(invalid()),
+    }
+}
+
+// Stores a field's value, returning `false` if the field was already set.
+fn set<T>(field: &mut Option<T>, value: T) -> bool {
+    field.replace(value).is_none()
+}
+
+fn invalid() -> TokenError {
+    TokenErrorKind::Decode("Invalid MessagePack data".to_string()).into()
+}
+
+// A value read from a header. Only the kinds header fields can hold are
+// kept; anything else is skipped over.
+enum Value<'a> {
+    Nil,
+    Uint(u64),
+    Str(&'a str),
+    // A map whose entries have not been read yet.
+    Map(usize),
+    Other,
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], TokenError> {
+        if len > self.0.len() {
+            return Err(invalid());
+        }
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    // Reads a big-endian unsigned integer of `len` bytes.
+    fn uint(&mut self, len: usize) -> Result<u64, TokenError> {
+        let bytes = self.take(len)?;
+        Ok(bytes.iter().fold(0, |value, &byte| (value << 8) | u64::from(byte)))
+    }
+
+    // Reads a big-endian signed integer of `len` bytes, which other
+    // encoders may use for small positive values too.
+    fn int(&mut self, len: usize) -> Result<Value<'a>, TokenError> {
+        let value = self.uint(len)?;
+        let negative = (value >> (len * 8 - 1)) & 1 == 1;
+        Ok(if negative { Value::Other } else { Value::Uint(value) })
+    }
+
+    // Reads a big-endian length of `len` bytes.
+    fn len(&mut self, len: usize) -> Result<usize, TokenError> {
+        usize::try_from(self.uint(len)?).map_err(|_| invalid())
+    }
+
+    // Reads the next value, with `depth` more levels of nesting allowed.
+    //
+    // A map is skipped if `skip_maps` is set, and otherwise returned with
+    // its entries left to be read. Arrays are always skipped.
+    fn value(&mut self, depth: usize, skip_maps: bool) -> Result<Value<'a>, TokenError> {
+        let marker = Marker::from_u8(self.take(1)?[0]);
+        let value = match marker {
+            Marker::Null => Value::Nil,
+            Marker::FixPos(value) => Value::Uint(value.into()),
+            Marker::U8 => Value::Uint(self.uint(1)?),
+            Marker::U16 => Value::Uint(self.uint(2)?),
+            Marker::U32 => Value::Uint(self.uint(4)?),
+            Marker::U64 => Value::Uint(self.uint(8)?),
+            Marker::FixStr(len) => self.str(len.into())?,
+            Marker::Str8 => self.str_with_len(1)?,
+            Marker::Str16 => self.str_with_len(2)?,
+            Marker::Str32 => self.str_with_len(4)?,
+            Marker::FixMap(len) => self.map(len.into(), depth, skip_maps)?,
+            Marker::Map16 => {
+                let len = self.len(2)?;
+                self.map(len, depth, skip_maps)?
+            }
+            Marker::Map32 => {
+                let len = self.len(4)?;
+                self.map(len, depth, skip_maps)?
+            }
+            Marker::FixArray(len) => self.skip_array(len.into(), depth)?,
+            Marker::Array16 => {
+                let len = self.len(2)?;
+                self.skip_array(len, depth)?
+            }
+            Marker::Array32 => {
+                let len = self.len(4)?;
+                self.skip_array(len, depth)?
+            }
+            Marker::True | Marker::False | Marker::FixNeg(_) => Value::Other,
+            Marker::I8 => self.int(1)?,
+            Marker::I16 => self.int(2)?,
+            Marker::I32 => self.int(4)?,
+            Marker::I64 => self.int(8)?,
+            Marker::F32 => self.skip(4)?,
+            Marker::F64 => self.skip(8)?,
+            Marker::Bin8 => self.skip_with_len(1, 0)?,
+            Marker::Bin16 => self.skip_with_len(2, 0)?,
+            Marker::Bin32 => self.skip_with_len(4, 0)?,
+            Marker::FixExt1 => self.skip(2)?,
+            Marker::FixExt2 => self.skip(3)?,
+            Marker::FixExt4 => self.skip(5)?,
+            Marker::FixExt8 => self.skip(9)?,
+            Marker::FixExt16 => self.skip(17)?,
+            // The length of extension data is followed by its type byte.
+            Marker::Ext8 => self.skip_with_len(1, 1)?,
+            Marker::Ext16 => self.skip_with_len(2, 1)?,
+            Marker::Ext32 => self.skip_with_len(4, 1)?,
+            Marker::Reserved => return Err(invalid()),
+        };
+        Ok(value)
+    }
+
+    fn str(&mut self, len: usize) -> Result<Value<'a>, TokenError> {
+        let bytes = self.take(len)?;
+        core::str::from_utf8(bytes).map(Value::Str).map_err(|_| invalid())
+    }
+
+    fn str_with_len(&mut self, len_bytes: usize) -> Result<Value<'a>, TokenError> {
+        let len = self.len(len_bytes)?;
+        self.str(len)
+    }
+
+    fn skip(&mut self, len: usize) -> Result<Value<'a>, TokenError> {
+        self.take(len)?;
+        Ok(Value::Other)
+    }
+
+    fn skip_with_len(&mut self, len_bytes: usize, extra: usize) -> Result<Value<'a>, TokenError> {
+        let len = self.len(len_bytes)?;
+        self.skip(len.checked_add(extra).ok_or_else(invalid)?)
+    }
+
+    fn map(&mut self, len: usize, depth: usize, skip: bool) -> Result<Value<'a>, TokenError> {
+        if depth == 0 {
+            return Err(TokenErrorKind::TooDeep.into());
+        }
+        if !skip {
+            return Ok(Value::Map(len));
+        }
+        for _ in 0..len {
+            self.value(depth - 1, true)?;
+            self.value(depth - 1, true)?;
+        }
+        Ok(Value::Other)
+    }
+
+    fn skip_array(&mut self, len: usize, depth: usize) -> Result<Value<'a>, TokenError> {
+        if depth == 0 {
+            return Err(TokenErrorKind::TooDeep.into());
+        }
+        for _ in 0..len {
+            self.value(depth - 1, true)?;
+        }
+        Ok(Value::Other)
+    }
+}
@@ -0,0 +1,99 @@
+use crate::{
+    caused_by, compute_signature, serialize_payload, Algorithm, Format, TokenError, TokenErrorKind,
+};
+#[cfg(feature = "std")]
+use crate::{
+    check_signature, check_token_len, decode_segment, deserialize_payload, now_millis,
+    signing_input, split_token, validate_claims, Encoding, Expirable, Validation,
+    DEFAULT_MAX_DEPTH, DEFAULT_MAX_TOKEN_LEN,
+};
+use alloc::format;
+use alloc::string::{String, ToString};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+
+// The JOSE header of a standard JWT. The algorithm is kept as its name,
+// so an unknown `alg` fails with `UnsupportedAlgorithm` once parsed.
+#[derive(Serialize, Deserialize)]
+struct JwtHeader {
+    alg: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    typ: Option<String>,
+}
+
+// Creates a standard JWT from the provided payload and secret.
+//
+// The token has a JSON header and payload and is signed with HS256 as in
+// RFC 7519, so it can be consumed by any JWT library. This is a bridge for
+// services that still expect JWTs; `create_token` remains the native
+// format.
+//
+// # Arguments
+//
+// * `payload` - The data to be serialized into the JWT's claims.
+// * `secret` - The secret key used to sign the JWT, as a string or raw bytes.
+//
+// # Returns
+//
+// * `Ok(String)` - The generated JWT string.
+// * `Err(TokenError)` - Any error that occurs during the JWT creation process.
+pub fn to_jwt<T>(payload: &T, secret: impl AsRef<[u8]>) -> Result<String, TokenError>
+where
+    T: Serialize,
+{
+    let header = JwtHeader {
+        alg: Algorithm::Hs256.name().to_string(),
+        typ: Some("JWT".to_string()),
+    };
+    let header_bytes = serde_json::to_vec(&header).map_err(|err| {
+        caused_by(TokenErrorKind::Serialize("Could not encode JSON".to_string()), err)
+    })?;
+    let payload_bytes = serialize_payload(Format::Json, payload)?;
+
+    let signing_input = format!(
+        "{}.{}",
+        URL_SAFE_NO_PAD.encode(header_bytes),
+        URL_SAFE_NO_PAD.encode(payload_bytes)
+    );
+    let signature = compute_signature(secret, signing_input.as_bytes())?;
+
+    Ok(format!("{}.{}", signing_input, URL_SAFE_NO_PAD.encode(signature)))
+}
+
+// Verifies a standard JWT and returns the decoded claims if valid.
+//
+// The JWT must be signed with HS256, HS384 or HS512; any other `alg`,
+// including `none`, is rejected. Its signature and `exp` and `nbf` claims
+// are checked as in `verify_token`, so JWTs from an existing issuer can be
+// accepted while moving to the native format.
+//
+// # Arguments
+//
+// * `secret` - The secret key used to verify the JWT's signature, as a string or raw bytes.
+// * `jwt` - The JWT string to be verified and decoded.
+//
+// # Returns
+//
+// * `Ok(T)` - The deserialized claims if the JWT is valid.
+// * `Err(TokenError)` - Any error that occurs during the verification process.
+#[cfg(feature = "std")]
+pub fn from_jwt<T>(secret: impl AsRef<[u8]>, jwt: &str) -> Result<T, TokenError>
+where
+    T: for<'de> Deserialize<'de> + Expirable,
+{
+    check_token_len(jwt, DEFAULT_MAX_TOKEN_LEN)?;
+    let (header, payload, signature) = split_token(jwt)?;
+
+    let header_bytes = decode_segment("header", header, Encoding::UrlSafeNoPad)?;
+    let header: JwtHeader = deserialize_payload(Format::Json, &header_bytes, DEFAULT_MAX_DEPTH)?;
+    let signature = decode_segment("signature", signature, Encoding::UrlSafeNoPad)?;
+    let signing_input = signing_input(jwt).as_bytes();
+    let alg = header.alg.parse()?;
+    check_signature(&[secret], alg, signing_input, &signature, &Algorithm::ALL)?;
+
+    let payload_bytes = decode_segment("payload", payload, Encoding::UrlSafeNoPad)?;
+    let claims: T = deserialize_payload(Format::Json, &payload_bytes, DEFAULT_MAX_DEPTH)?;
+    validate_claims(&claims, &Validation::default(), now_millis(), Vec::new())?;
+    Ok(claims)
+}
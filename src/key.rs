@@ -0,0 +1,236 @@
+use crate::{HmacSha256, TokenError, TokenErrorKind};
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use core::ptr;
+use core::sync::atomic::{compiler_fence, Ordering};
+use hmac::Mac;
+
+// The length of keys produced by `SecretKey::derive`, the SHA-256 output size.
+const DERIVED_KEY_LEN: usize = 32;
+
+// The shortest secret accepted for signing or verifying, in bytes.
+//
+// A secret shorter than the SHA-256 output is weaker than the MAC it keys,
+// and an empty one usually means the secret was never configured, such as
+// an unset environment variable. Tests that need shorter secrets can key a
+// `Signer` or `Verifier` with `new_unchecked`.
+pub const MIN_SECRET_LEN: usize = 32;
+
+// A secret key that wipes its bytes from memory when dropped.
+//
+// `SecretKey` can be passed anywhere a secret is accepted, since it
+// implements `AsRef<[u8]>`. The key bytes are overwritten with zeros as
+// soon as the key goes out of scope, so they don't linger in freed heap
+// memory. Its `Debug` output never includes the key bytes.
+//
+// The crate never copies the key bytes itself while signing or verifying;
+// the only other copy is the keyed state held by the `hmac` crate for the
+// duration of a single call, or for the lifetime of a `Signer` or
+// `Verifier`. That keyed state is not wiped when it is dropped.
+pub struct SecretKey {
+    bytes: Vec<u8>,
+}
+
+impl SecretKey {
+    // Creates a secret key from the provided bytes.
+    //
+    // Passing an owned `Vec<u8>` or `String` moves the bytes into the key
+    // without copying them.
+    pub fn new(bytes: impl Into<Vec<u8>>) -> Self {
+        SecretKey {
+            bytes: bytes.into(),
+        }
+    }
+
+    // Derives a 32-byte MAC key from a master secret with HKDF-SHA256.
+    //
+    // This follows RFC 5869: the master secret is extracted into a
+    // pseudorandom key with `salt` (an empty salt uses the all-zero default)
+    // and expanded with `info`, which binds the key to its purpose so one
+    // master secret can yield independent keys for different uses. The
+    // derived key can be used anywhere an explicit key is accepted.
+    //
+    // HKDF is not password hashing. It assumes the master secret already
+    // has reasonable entropy, so derive keys from low-entropy passwords with
+    // a slow password hash such as Argon2 instead.
+    //
+    // # Arguments
+    //
+    // * `master` - The master secret to derive the key from.
+    // * `salt` - An optional, non-secret random value; may be empty.
+    // * `info` - Context identifying what the key is used for; may be empty.
+    //
+    // # Returns
+    //
+    // * `Ok(SecretKey)` - The derived key.
+    // * `Err(TokenError)` - The key could not be derived.
+    pub fn derive(
+        master: impl AsRef<[u8]>,
+        salt: impl AsRef<[u8]>,
+        info: impl AsRef<[u8]>,
+    ) -> Result<Self, TokenError> {
+        let salt = salt.as_ref();
+        let salt = if salt.is_empty() { &[0; DERIVED_KEY_LEN][..] } else { salt };
+
+        // Extract: PRK = HMAC(salt, master).
+        let mut extract = <HmacSha256 as Mac>::new_from_slice(salt)?;
+        extract.update(master.as_ref());
+        let mut prk = extract.finalize().into_bytes();
+
+        // Expand: a 32-byte key is the first output block, HMAC(PRK, info || 0x01).
+        let expand = <HmacSha256 as Mac>::new_from_slice(&prk);
+        wipe(&mut prk);
+        let mut expand = expand?;
+        expand.update(info.as_ref());
+        expand.update(&[1]);
+        let mut okm = expand.finalize().into_bytes();
+
+        let key = SecretKey::new(okm.as_slice());
+        wipe(&mut okm);
+        Ok(key)
+    }
+
+    // Returns the raw key bytes.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    // Overwrites the key bytes with zeros.
+    //
+    // This is done automatically when the key is dropped.
+    pub fn zeroize(&mut self) {
+        wipe(&mut self.bytes);
+    }
+}
+
+impl Drop for SecretKey {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl Clone for SecretKey {
+    fn clone(&self) -> Self {
+        SecretKey::new(self.bytes.as_slice())
+    }
+}
+
+impl AsRef<[u8]> for SecretKey {
+    fn as_ref(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+impl From<Vec<u8>> for SecretKey {
+    fn from(bytes: Vec<u8>) -> Self {
+        SecretKey::new(bytes)
+    }
+}
+
+impl From<&[u8]> for SecretKey {
+    fn from(bytes: &[u8]) -> Self {
+        SecretKey::new(bytes)
+    }
+}
+
+impl From<String> for SecretKey {
+    fn from(secret: String) -> Self {
+        SecretKey::new(secret)
+    }
+}
+
+impl From<&str> for SecretKey {
+    fn from(secret: &str) -> Self {
+        SecretKey::new(secret)
+    }
+}
+
+impl fmt::Debug for SecretKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SecretKey([REDACTED])")
+    }
+}
+
+// A secret key of exactly `N` bytes, held inline rather than on the heap.
+//
+// This is `SecretKey` for `no_std` targets that keep keys on the stack or
+// in static memory. It is accepted anywhere a secret is, and its bytes are
+// wiped when it is dropped. Keys shorter than `MIN_SECRET_LEN` fail to
+// compile.
+pub struct FixedSecretKey<const N: usize> {
+    bytes: [u8; N],
+}
+
+impl<const N: usize> FixedSecretKey<N> {
+    // Fails the build when `new` is used with a key that is too short.
+    const LONG_ENOUGH: () = assert!(
+        N >= MIN_SECRET_LEN,
+        "secret keys must be at least MIN_SECRET_LEN (32) bytes long"
+    );
+
+    // Creates a secret key from the provided bytes.
+    pub const fn new(bytes: [u8; N]) -> Self {
+        let () = Self::LONG_ENOUGH;
+        FixedSecretKey { bytes }
+    }
+
+    // Returns the raw key bytes.
+    pub fn as_bytes(&self) -> &[u8; N] {
+        &self.bytes
+    }
+
+    // Overwrites the key bytes with zeros.
+    //
+    // This is done automatically when the key is dropped.
+    pub fn zeroize(&mut self) {
+        wipe(&mut self.bytes);
+    }
+}
+
+impl<const N: usize> Drop for FixedSecretKey<N> {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl<const N: usize> Clone for FixedSecretKey<N> {
+    fn clone(&self) -> Self {
+        FixedSecretKey::new(self.bytes)
+    }
+}
+
+impl<const N: usize> AsRef<[u8]> for FixedSecretKey<N> {
+    fn as_ref(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+impl<const N: usize> From<[u8; N]> for FixedSecretKey<N> {
+    fn from(bytes: [u8; N]) -> Self {
+        FixedSecretKey::new(bytes)
+    }
+}
+
+impl<const N: usize> fmt::Debug for FixedSecretKey<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "FixedSecretKey([REDACTED])")
+    }
+}
+
+// Rejects secrets shorter than `MIN_SECRET_LEN`.
+pub(crate) fn check_secret(secret: &[u8]) -> Result<(), TokenError> {
+    if secret.len() < MIN_SECRET_LEN {
+        return Err(TokenErrorKind::WeakKey.into());
+    }
+    Ok(())
+}
+
+// Overwrites the bytes with zeros in a way the compiler won't optimize out.
+fn wipe(bytes: &mut [u8]) {
+    for byte in bytes.iter_mut() {
+        // Safety: `byte` is a valid, aligned, exclusive reference.
+        unsafe { ptr::write_volatile(byte, 0) };
+    }
+    compiler_fence(Ordering::SeqCst);
+}
@@ -0,0 +1,164 @@
+use crate::mac::KeyedMac;
+#[cfg(feature = "std")]
+use crate::now_millis;
+use crate::{
+    unverified_header, verify_token_inner, Clock, Expirable, TokenError, TokenErrorKind,
+    Validation,
+};
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use core::fmt;
+use serde::Deserialize;
+
+// A set of keys indexed by key id, like a JWKS.
+//
+// Each token is verified with the key matching the `kid` in its header,
+// so keys published out of band can be rotated without touching the code
+// verifying tokens: fetch and parse them yourself, then `reload` the set.
+// Every key is keyed once up front, as in `Verifier`. Tokens without a
+// key id fail with `MissingKeyId`, and tokens whose key id is not in the
+// set fail with `UnknownKeyId`.
+#[derive(Clone, Default)]
+pub struct KeySet {
+    keys: BTreeMap<String, KeyedMac>,
+    validation: Validation,
+}
+
+impl KeySet {
+    // Creates an empty set checking tokens like `verify_token`.
+    pub fn new() -> Self {
+        KeySet::default()
+    }
+
+    // Creates an empty set checking tokens like `verify` with `validation`.
+    pub fn with_validation(validation: Validation) -> Self {
+        KeySet {
+            keys: BTreeMap::new(),
+            validation,
+        }
+    }
+
+    // Adds a key, replacing any key with the same id.
+    pub fn insert(
+        &mut self,
+        kid: impl Into<String>,
+        secret: impl AsRef<[u8]>,
+    ) -> Result<(), TokenError> {
+        self.keys.insert(kid.into(), KeyedMac::new(secret.as_ref())?);
+        Ok(())
+    }
+
+    // Removes the key with this id, returning whether it was in the set.
+    pub fn remove(&mut self, kid: &str) -> bool {
+        self.keys.remove(kid).is_some()
+    }
+
+    // Replaces every key in the set with `keys`.
+    //
+    // The new keys are all keyed before any is swapped in, so if one of
+    // them is rejected the set is left as it was.
+    //
+    // # Arguments
+    //
+    // * `keys` - The key ids and secrets to verify tokens with from now on.
+    //
+    // # Returns
+    //
+    // * `Ok(())` - The set now holds exactly `keys`.
+    // * `Err(TokenError)` - A secret was rejected, such as one that is too short.
+    pub fn reload<I, K, S>(&mut self, keys: I) -> Result<(), TokenError>
+    where
+        I: IntoIterator<Item = (K, S)>,
+        K: Into<String>,
+        S: AsRef<[u8]>,
+    {
+        let mut reloaded = BTreeMap::new();
+        for (kid, secret) in keys {
+            reloaded.insert(kid.into(), KeyedMac::new(secret.as_ref())?);
+        }
+        self.keys = reloaded;
+        Ok(())
+    }
+
+    // Returns whether the set has a key with this id.
+    pub fn contains(&self, kid: &str) -> bool {
+        self.keys.contains_key(kid)
+    }
+
+    // Returns the ids of the keys in the set, in order.
+    pub fn kids(&self) -> impl Iterator<Item = &str> {
+        self.keys.keys().map(String::as_str)
+    }
+
+    // Returns the number of keys in the set.
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    // Returns whether the set has no keys.
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    // Returns the checks run on every token.
+    pub fn validation(&self) -> &Validation {
+        &self.validation
+    }
+
+    // Verifies a token with the key matching its key id and returns the
+    // decoded payload if valid.
+    //
+    // # Arguments
+    //
+    // * `token` - The token string to be verified and decoded.
+    //
+    // # Returns
+    //
+    // * `Ok(T)` - The deserialized payload if the token is valid.
+    // * `Err(TokenError)` - `MissingKeyId` or `UnknownKeyId` if the set has no key for the
+    //   token, or any other error that occurs during the verification process.
+    #[cfg(feature = "std")]
+    pub fn verify<T>(&self, token: &str) -> Result<T, TokenError>
+    where
+        T: for<'de> Deserialize<'de> + Expirable,
+    {
+        verify_token_inner(self.key_for(token)?, token, &self.validation, now_millis())
+    }
+
+    // Verifies a token as of the given time (Unix timestamp).
+    pub fn verify_at<T>(&self, token: &str, now: i64) -> Result<T, TokenError>
+    where
+        T: for<'de> Deserialize<'de> + Expirable,
+    {
+        let now_millis = now.saturating_mul(1000);
+        verify_token_inner(self.key_for(token)?, token, &self.validation, now_millis)
+    }
+
+    // Verifies a token with the current time read from `clock`.
+    pub fn verify_with_clock<T>(&self, token: &str, clock: &impl Clock) -> Result<T, TokenError>
+    where
+        T: for<'de> Deserialize<'de> + Expirable,
+    {
+        verify_token_inner(self.key_for(token)?, token, &self.validation, clock.now_millis())
+    }
+
+    // Returns the key matching the key id in the token's header, which is
+    // not verified yet.
+    fn key_for(&self, token: &str) -> Result<&KeyedMac, TokenError> {
+        let header = unverified_header(token, &self.validation)?;
+        let kid = header.kid.ok_or(TokenErrorKind::MissingKeyId)?;
+        self.keys
+            .get(&kid)
+            .ok_or_else(|| TokenErrorKind::UnknownKeyId.into())
+    }
+}
+
+// Never prints the keyed state, which is derived from the secrets.
+impl fmt::Debug for KeySet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeySet")
+            .field("kids", &self.keys.keys())
+            .field("validation", &self.validation)
+            .finish()
+    }
+}
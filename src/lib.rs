@@ -1,149 +1,2992 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
 use serde::{Deserialize, Serialize};
+use hmac::digest::{Digest, InvalidLength, KeyInit, OutputSizeUser};
 use hmac::{Hmac, Mac};
-use sha2::Sha256;
-use rmp_serde::{from_slice, to_vec};
-use base64::engine::general_purpose::URL_SAFE_NO_PAD;
-use base64::Engine;
-use chrono::Utc;
-use std::error::Error;
-use std::fmt;
+#[cfg(feature = "msgpack")]
+use rmp_serde::{to_vec, to_vec_named};
+use base64::engine::general_purpose::{
+    GeneralPurpose, GeneralPurposeConfig, STANDARD, STANDARD_NO_PAD, URL_SAFE, URL_SAFE_NO_PAD,
+};
+use base64::engine::DecodePaddingMode;
+use base64::{alphabet, DecodeError, DecodeSliceError, Engine};
+use alloc::borrow::Cow;
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::{String, ToString};
+#[cfg(feature = "std")]
+use alloc::vec;
+use alloc::vec::Vec;
+use core::error::Error;
+use core::fmt;
+use core::fmt::Write as _;
+use core::str::FromStr;
+#[cfg(feature = "std")]
+use core::future::Future;
+#[cfg(feature = "std")]
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::io;
+#[cfg(all(feature = "std", feature = "chrono"))]
+use chrono::{DateTime, Utc};
+
+mod canonical;
+mod claims;
+mod clock;
+mod cookie;
+mod header;
+mod jwt;
+mod key;
+mod keyset;
+mod mac;
+mod nonce;
+#[cfg(feature = "std")]
+mod pair;
+mod provider;
+mod schema;
+mod shape;
+mod signer;
+mod stream;
+mod token;
+mod validation;
+
+pub use claims::{Audience, Claims, ClaimsBuilder, Subject};
+#[cfg(feature = "std")]
+pub use clock::SystemClock;
+pub use clock::{Clock, FixedClock};
+pub use cookie::{
+    from_cookie_chunks, from_cookie_value, to_cookie_chunks, to_cookie_value, MAX_COOKIE_VALUE_LEN,
+};
+#[cfg(feature = "std")]
+pub use jwt::from_jwt;
+pub use jwt::to_jwt;
+pub use key::{FixedSecretKey, SecretKey, MIN_SECRET_LEN};
+pub use keyset::KeySet;
+#[cfg(feature = "std")]
+pub use nonce::RandomIds;
+pub use nonce::{IdSource, NonceStore, SeededIds};
+#[cfg(feature = "std")]
+pub use pair::{
+    create_pair, create_pair_with, rotate, rotate_with, TokenPair, ACCESS_TOKEN_TYPE,
+    REFRESH_TOKEN_TYPE,
+};
+#[cfg(feature = "std")]
+pub use provider::{verify_with_provider, verify_with_provider_async};
+pub use provider::{AsyncKeyProvider, KeyFuture, KeyProvider};
+pub use schema::{ClaimConstraint, ClaimRule, ClaimType, ClaimsSchema};
+pub use mac::{constant_time_eq, HmacDigest};
+pub use sha2::{Sha256, Sha384, Sha512};
+pub use signer::{KeyMatch, Signer, TypedSigner, TypedVerifier, Verifier};
+pub use stream::{StreamSigner, StreamVerifier};
+pub use token::Token;
+pub use validation::{Check, Validation, DEFAULT_MAX_DEPTH, DEFAULT_MAX_TOKEN_LEN};
+
+use canonical::Canonical;
+use key::check_secret;
+#[cfg(feature = "std")]
+use mac::{KeyedMac, WithAad};
+use mac::{with_aad, SignatureVerifier};
+#[cfg(feature = "std")]
+use validation::{checks_run, duration_secs};
+use validation::{check_payload_claims, collected_error, validate_claims};
 
 type HmacSha256 = Hmac<Sha256>;
+type HmacSha384 = Hmac<Sha384>;
+type HmacSha512 = Hmac<Sha512>;
+
+// The reason a token operation failed.
+//
+// Callers can match on the kind to tell apart, for example, an expired
+// token (refresh it) from a tampered one (reject it).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TokenErrorKind {
+    // The token does not have the expected `header.payload.signature` shape.
+    InvalidFormat,
+    // A segment of the token, named here, has characters outside the
+    // expected base64 alphabet, or misplaced padding.
+    InvalidEncoding(&'static str),
+    // The signature does not match the token contents.
+    InvalidSignature,
+    // The signature is not as long as the algorithm's signatures, so the
+    // token was truncated or corrupted.
+    BadSignatureLength,
+    // The token uses a format version this crate doesn't support.
+    UnsupportedVersion(u8),
+    // The token was signed with a different algorithm than expected.
+    AlgorithmMismatch,
+    // The token header names an algorithm this crate doesn't support, such
+    // as `none`.
+    UnsupportedAlgorithm(String),
+    // The token's expiration time has passed.
+    Expired,
+    // The token's expiration time is further away than
+    // `Validation::max_lifetime` allows.
+    LifetimeTooLong,
+    // The token's "not before" time has not been reached yet.
+    NotYetValid,
+    // The token's `iat` is later than now, even allowing for the leeway.
+    IssuedInFuture,
+    // The token was issued longer ago than the maximum age allows.
+    TooOld,
+    // The token's version is below `Validation::min_version`.
+    StaleVersion,
+    // The token has been revoked.
+    Revoked,
+    // The single-use token has already been used.
+    AlreadyUsed,
+    // The token has no uses left.
+    Exhausted,
+    // The token was not issued by the expected issuer.
+    InvalidIssuer,
+    // The token is not intended for the expected audience.
+    InvalidAudience,
+    // The token is not of the expected type.
+    InvalidType,
+    // A claim the validation requires is missing or null.
+    MissingClaim(String),
+    // A claim has a value that is not valid for it, such as an empty `sub`.
+    InvalidClaim(String),
+    // A scope the validation requires is not granted by the token.
+    MissingScope(String),
+    // The token is longer than the maximum length allowed.
+    TooLarge,
+    // The payload nests arrays and maps deeper than allowed.
+    TooDeep,
+    // The payload is a single value, such as an integer or a string, but
+    // the type it is decoded into is read from a map or an array.
+    PayloadTypeMismatch {
+        // What the type expects, such as `struct Claims`.
+        expected: String,
+        // What the payload holds, such as `an integer`.
+        found: &'static str,
+    },
+    // The secret cannot be used as a key for the algorithm.
+    InvalidKey,
+    // The secret is shorter than `MIN_SECRET_LEN`.
+    WeakKey,
+    // The token header does not name the key it was signed with.
+    MissingKeyId,
+    // The token was signed with a key the verifier doesn't know.
+    UnknownKeyId,
+    // The token has no id, so it can't be tracked as used.
+    MissingTokenId,
+    // A segment could not be decoded or deserialized.
+    Decode(String),
+    // The header or payload could not be serialized.
+    Serialize(String),
+    // The token could not be written.
+    Io(String),
+    // Several claims failed their checks, when `Validation::collect_errors`
+    // is set. Listed in the order the checks run.
+    Multiple(Vec<TokenErrorKind>),
+}
 
 // Custom error type for token-related errors.
+//
+// Errors raised by a dependency (base64, MessagePack, JSON, HMAC keying or
+// I/O) keep that error as their `source`, so error reporters can show the
+// whole chain. The message itself then only says what was being done.
 #[derive(Debug)]
-pub struct TokenError(String);
+pub struct TokenError {
+    kind: TokenErrorKind,
+    source: Option<Box<dyn Error + Send + Sync>>,
+}
+
+impl TokenError {
+    // Returns the reason the operation failed.
+    pub fn kind(&self) -> &TokenErrorKind {
+        &self.kind
+    }
+}
+
+// Creates an error of the given kind caused by `source`.
+#[cfg(feature = "std")]
+fn caused_by<E>(kind: TokenErrorKind, source: E) -> TokenError
+where
+    E: Error + Send + Sync + 'static,
+{
+    TokenError {
+        kind,
+        source: Some(Box::new(source)),
+    }
+}
+
+// Creates an error for a payload that failed to deserialize. With the
+// `redact-claims` feature the serde error is dropped, since its message
+// can quote the value of a claim.
+#[cfg(not(feature = "redact-claims"))]
+fn payload_error<E>(kind: TokenErrorKind, source: E) -> TokenError
+where
+    E: Error + Send + Sync + 'static,
+{
+    caused_by(kind, source)
+}
+
+#[cfg(feature = "redact-claims")]
+fn payload_error<E>(kind: TokenErrorKind, _source: E) -> TokenError {
+    kind.into()
+}
+
+// Without `std` the base64 and HMAC errors do not implement `Error`, so the
+// cause is appended to the message instead of being kept as the source.
+#[cfg(not(feature = "std"))]
+fn caused_by<E>(kind: TokenErrorKind, source: E) -> TokenError
+where
+    E: fmt::Display,
+{
+    match kind {
+        TokenErrorKind::Decode(msg) => TokenErrorKind::Decode(format!("{}: {}", msg, source)),
+        TokenErrorKind::Serialize(msg) => {
+            TokenErrorKind::Serialize(format!("{}: {}", msg, source))
+        }
+        kind => kind,
+    }
+    .into()
+}
 
 impl fmt::Display for TokenError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.0)
+        fmt::Display::fmt(&self.kind, f)
+    }
+}
+
+impl fmt::Display for TokenErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenErrorKind::InvalidFormat => write!(f, "Invalid token format"),
+            TokenErrorKind::InvalidEncoding(segment) => {
+                write!(f, "Invalid base64 encoding in token {} segment", segment)
+            }
+            TokenErrorKind::InvalidSignature => write!(f, "Invalid token signature"),
+            TokenErrorKind::BadSignatureLength => write!(f, "Token signature has the wrong length"),
+            TokenErrorKind::UnsupportedVersion(ver) => write!(f, "Unsupported token version {}", ver),
+            TokenErrorKind::AlgorithmMismatch => write!(f, "Token algorithm mismatch"),
+            TokenErrorKind::UnsupportedAlgorithm(alg) => {
+                write!(f, "Unsupported token algorithm {:?}", alg)
+            }
+            TokenErrorKind::Expired => write!(f, "Token has expired"),
+            TokenErrorKind::LifetimeTooLong => write!(f, "Token expires too far in the future"),
+            TokenErrorKind::NotYetValid => write!(f, "Token is not yet valid"),
+            TokenErrorKind::IssuedInFuture => write!(f, "Token was issued in the future"),
+            TokenErrorKind::TooOld => write!(f, "Token is too old"),
+            TokenErrorKind::StaleVersion => write!(f, "Token version is no longer accepted"),
+            TokenErrorKind::Revoked => write!(f, "Token has been revoked"),
+            TokenErrorKind::AlreadyUsed => write!(f, "Token has already been used"),
+            TokenErrorKind::Exhausted => write!(f, "Token has no uses left"),
+            TokenErrorKind::InvalidIssuer => write!(f, "Invalid token issuer"),
+            TokenErrorKind::InvalidAudience => write!(f, "Invalid token audience"),
+            TokenErrorKind::InvalidType => write!(f, "Invalid token type"),
+            TokenErrorKind::MissingClaim(name) => write!(f, "Token is missing the {} claim", name),
+            TokenErrorKind::InvalidClaim(name) => write!(f, "Token has an invalid {} claim", name),
+            TokenErrorKind::MissingScope(name) => write!(f, "Token is missing the {} scope", name),
+            TokenErrorKind::TooLarge => write!(f, "Token is too large"),
+            TokenErrorKind::TooDeep => write!(f, "Token payload is nested too deeply"),
+            TokenErrorKind::PayloadTypeMismatch { expected, found } => {
+                write!(f, "Token payload is {}, expected {}", found, expected)
+            }
+            TokenErrorKind::InvalidKey => write!(f, "Invalid secret key"),
+            TokenErrorKind::WeakKey => write!(f, "Secret key is too short"),
+            TokenErrorKind::MissingKeyId => write!(f, "Token has no key id"),
+            TokenErrorKind::UnknownKeyId => write!(f, "Unknown token key id"),
+            TokenErrorKind::MissingTokenId => write!(f, "Token has no id"),
+            TokenErrorKind::Decode(msg) => write!(f, "{}", msg),
+            TokenErrorKind::Serialize(msg) => write!(f, "{}", msg),
+            TokenErrorKind::Io(msg) => write!(f, "{}", msg),
+            TokenErrorKind::Multiple(kinds) => {
+                write!(f, "Token failed {} checks", kinds.len())?;
+                for (i, kind) in kinds.iter().enumerate() {
+                    write!(f, "{} {}", if i == 0 { ":" } else { ";" }, kind)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Error for TokenError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source.as_deref().map(|err| err as &(dyn Error + 'static))
+    }
+}
+
+impl From<TokenErrorKind> for TokenError {
+    fn from(kind: TokenErrorKind) -> Self {
+        TokenError { kind, source: None }
+    }
+}
+
+impl From<base64::DecodeError> for TokenError {
+    fn from(err: base64::DecodeError) -> Self {
+        caused_by(TokenErrorKind::Decode("Invalid base64".to_string()), err)
+    }
+}
+
+#[cfg(feature = "msgpack")]
+impl From<rmp_serde::decode::Error> for TokenError {
+    fn from(err: rmp_serde::decode::Error) -> Self {
+        match err {
+            rmp_serde::decode::Error::DepthLimitExceeded => TokenErrorKind::TooDeep.into(),
+            err => {
+                let kind = TokenErrorKind::Decode("Invalid MessagePack data".to_string());
+                payload_error(kind, err)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "msgpack")]
+impl From<rmp_serde::encode::Error> for TokenError {
+    fn from(err: rmp_serde::encode::Error) -> Self {
+        caused_by(TokenErrorKind::Serialize("Could not encode MessagePack".to_string()), err)
+    }
+}
+
+impl From<InvalidLength> for TokenError {
+    fn from(err: InvalidLength) -> Self {
+        caused_by(TokenErrorKind::InvalidKey, err)
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<io::Error> for TokenError {
+    fn from(err: io::Error) -> Self {
+        caused_by(TokenErrorKind::Io("Could not write token".to_string()), err)
+    }
+}
+
+// The error returned by `verify_token_or_expired`.
+//
+// An expired token still carries its payload, which is trustworthy since
+// the signature was checked before the expiration.
+#[cfg_attr(not(feature = "redact-claims"), derive(Debug))]
+pub enum VerifyError<T> {
+    // The token is authentic but has expired.
+    Expired(T),
+    // The token failed verification for any other reason.
+    Invalid(TokenError),
+}
+
+impl<T> fmt::Display for VerifyError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerifyError::Expired(_) => write!(f, "{}", TokenError::from(TokenErrorKind::Expired)),
+            VerifyError::Invalid(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+// Leaves the payload out, since it may hold personal data.
+#[cfg(feature = "redact-claims")]
+impl<T> fmt::Debug for VerifyError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerifyError::Expired(_) => f.write_str("Expired(..)"),
+            VerifyError::Invalid(err) => f.debug_tuple("Invalid").field(err).finish(),
+        }
+    }
+}
+
+impl<T: fmt::Debug> Error for VerifyError<T> {}
+
+impl<T> From<TokenError> for VerifyError<T> {
+    fn from(err: TokenError) -> Self {
+        VerifyError::Invalid(err)
+    }
+}
+
+// The HMAC algorithm used to sign a token.
+//
+// The algorithm is recorded in the token header so the verifier can pick the
+// matching HMAC implementation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Algorithm {
+    #[default]
+    #[serde(rename = "HS256")]
+    Hs256,
+    #[serde(rename = "HS384")]
+    Hs384,
+    #[serde(rename = "HS512")]
+    Hs512,
+}
+
+impl Algorithm {
+    // Every supported algorithm, which is what `Validation` accepts by default.
+    //
+    // There is deliberately no algorithm for unsigned tokens, so a token
+    // declaring `"none"` fails with `UnsupportedAlgorithm`.
+    pub const ALL: [Algorithm; 3] = [Algorithm::Hs256, Algorithm::Hs384, Algorithm::Hs512];
+
+    // Returns the length of the signatures the algorithm produces, in bytes.
+    pub fn signature_len(self) -> usize {
+        match self {
+            Algorithm::Hs256 => <HmacSha256 as OutputSizeUser>::output_size(),
+            Algorithm::Hs384 => <HmacSha384 as OutputSizeUser>::output_size(),
+            Algorithm::Hs512 => <HmacSha512 as OutputSizeUser>::output_size(),
+        }
+    }
+
+    // Returns the name the algorithm is recorded under in token headers,
+    // such as `HS256`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Algorithm::Hs256 => "HS256",
+            Algorithm::Hs384 => "HS384",
+            Algorithm::Hs512 => "HS512",
+        }
+    }
+}
+
+// Parses an algorithm from the name recorded in token headers. Any other
+// name, including `none`, fails with `UnsupportedAlgorithm`.
+impl FromStr for Algorithm {
+    type Err = TokenError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Algorithm::ALL
+            .into_iter()
+            .find(|alg| alg.name() == name)
+            .ok_or_else(|| TokenErrorKind::UnsupportedAlgorithm(name.into()).into())
+    }
+}
+
+// The encoding used for the payload segment of a token.
+//
+// MessagePack keeps tokens compact, while JSON lets services that can't
+// parse MessagePack read the payload. The format is recorded in the token
+// header so the payload is always decoded the same way it was encoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Format {
+    #[default]
+    #[serde(rename = "msgpack")]
+    MessagePack,
+    #[serde(rename = "json")]
+    Json,
+    // The payload bytes are carried as they are, see `create_token_raw`.
+    #[serde(rename = "raw")]
+    Raw,
+}
+
+// The base64 variant the segments of a token are encoded with.
+//
+// Tokens are base64url encoded without padding unless configured
+// otherwise. The other variants exist for consumers that predate
+// url-safe base64. The encoding can't be recorded in the token, since the
+// header itself is base64 encoded, so the verifier must be configured with
+// the same encoding the token was created with. Padding is optional when
+// decoding any of them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Encoding {
+    // The url-safe alphabet (`-` and `_`) without `=` padding.
+    #[default]
+    UrlSafeNoPad,
+    // The url-safe alphabet (`-` and `_`) with `=` padding.
+    UrlSafe,
+    // The standard alphabet (`+` and `/`) without `=` padding.
+    StandardNoPad,
+    // The standard alphabet (`+` and `/`) with `=` padding.
+    Standard,
+}
+
+impl Encoding {
+    // Returns the engine used to encode segments.
+    fn encoder(self) -> &'static GeneralPurpose {
+        match self {
+            Encoding::UrlSafeNoPad => &URL_SAFE_NO_PAD,
+            Encoding::UrlSafe => &URL_SAFE,
+            Encoding::StandardNoPad => &STANDARD_NO_PAD,
+            Encoding::Standard => &STANDARD,
+        }
+    }
+
+    // Returns the engine used to decode segments, accepting any padding.
+    fn decoder(self) -> &'static GeneralPurpose {
+        match self {
+            Encoding::UrlSafeNoPad | Encoding::UrlSafe => &URL_SAFE_LENIENT,
+            Encoding::StandardNoPad | Encoding::Standard => &STANDARD_LENIENT,
+        }
+    }
+}
+
+// Options controlling how a token is created.
+#[derive(Debug, Clone)]
+pub struct TokenOptions {
+    // The HMAC algorithm used to sign the token.
+    pub alg: Algorithm,
+    // The encoding used for the payload.
+    pub format: Format,
+    // The id of the signing key, recorded so verifiers can select the key.
+    pub kid: Option<String>,
+    // The type of the token, checked against `Validation::expected_typ`.
+    pub typ: Option<String>,
+    // The base64 variant the segments are encoded with.
+    pub encoding: Encoding,
+    // Whether MessagePack payloads encode structs as maps keyed by field
+    // name rather than as arrays of their fields in order. On by default:
+    // maps are larger, but keep decoding when fields are added, removed or
+    // reordered, and can be decoded into a struct with only some of the
+    // fields. Either is decoded the same way, so verifiers need no matching
+    // setting and tokens created before this default still verify.
+    pub named_fields: bool,
+    // Whether to serialize the payload canonically, so payloads with the
+    // same data always produce the same bytes and signature.
+    //
+    // The payload is first converted to a dynamic value, then written with
+    // map keys sorted by their UTF-8 bytes and every integer in its shortest
+    // encoding. Structs are always written as maps in this mode. Off by
+    // default, since the conversion costs time, and because it changes a few
+    // encodings: byte strings become arrays of numbers, maps with non-string
+    // keys are rejected, and non-finite floats become null. Verifiers need
+    // no matching setting.
+    pub canonical: bool,
+}
+
+impl Default for TokenOptions {
+    fn default() -> Self {
+        TokenOptions {
+            alg: Algorithm::default(),
+            format: Format::default(),
+            kid: None,
+            typ: None,
+            encoding: Encoding::default(),
+            named_fields: true,
+            canonical: false,
+        }
+    }
+}
+
+// The version of the token format written by this crate.
+//
+// Version 0 is the original two-segment `payload.signature` format, which
+// had no header at all.
+const TOKEN_VERSION: u8 = 1;
+
+// The header segment of a token, carrying the metadata needed to verify it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Header {
+    // The version of the token format.
+    #[serde(default = "current_version")]
+    pub ver: u8,
+    // The algorithm the token says it was signed with.
+    pub alg: Algorithm,
+    // The encoding of the payload.
+    #[serde(default)]
+    pub fmt: Format,
+    // The id of the signing key, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kid: Option<String>,
+    // The type of the token, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub typ: Option<String>,
+}
+
+fn current_version() -> u8 {
+    TOKEN_VERSION
+}
+
+// Deserializes a header segment, rejecting token versions and algorithms
+// this crate can't read.
+fn read_header(header_bytes: &[u8]) -> Result<Header, TokenError> {
+    let header = header::read(header_bytes, DEFAULT_MAX_DEPTH)?;
+    if header.ver != TOKEN_VERSION {
+        return Err(TokenErrorKind::UnsupportedVersion(header.ver).into());
     }
+    Ok(Header {
+        ver: header.ver,
+        alg: header.alg.parse()?,
+        fmt: header.fmt,
+        kid: header.kid,
+        typ: header.typ,
+    })
 }
 
-impl Error for TokenError {}
+// A trait for payloads whose expiration can be set by the crate.
+//
+// Implemented by payloads passed to `create_token_with_ttl`.
+pub trait SetExpiry {
+    // Sets the expiration timestamp (Unix timestamp).
+    fn set_exp(&mut self, exp: i64);
+}
 
-// A trait to define the expiration time for tokens.
+// A trait to define the expiration time and other claims checked for tokens.
 pub trait Expirable {
     // Returns the expiration timestamp (Unix timestamp).
+    //
+    // As in RFC 7519, the token is valid strictly before this time: it is
+    // rejected as expired from the second `now == exp` onwards.
     fn exp(&self) -> i64;
+
+    // Returns the expiration timestamp in milliseconds, if the token has one.
+    //
+    // Short-lived tokens can use this for sub-second expiry. When it returns
+    // `Some`, it is checked instead of `exp`. The default returns `None`.
+    fn exp_millis(&self) -> Option<i64> {
+        None
+    }
+
+    // Returns the "not before" timestamp (Unix timestamp), if the token has one.
+    //
+    // Tokens are rejected until this time has been reached. The default
+    // returns `None`, meaning the token is valid as soon as it is issued.
+    fn nbf(&self) -> Option<i64> {
+        None
+    }
+
+    // Returns the issued-at timestamp (Unix timestamp), if the token has one.
+    //
+    // Tokens issued later than now plus the leeway are rejected, and the
+    // age of the token is checked against `Validation::max_age` if set.
+    fn iat(&self) -> Option<i64> {
+        None
+    }
+
+    // Returns the issuer of the token, if it has one.
+    //
+    // Only checked when `Validation::expected_iss` is set.
+    fn iss(&self) -> Option<&str> {
+        None
+    }
+
+    // Returns the unique id of the token, if it has one.
+    //
+    // Used to look the token up when checking whether it has been revoked.
+    fn jti(&self) -> Option<&str> {
+        None
+    }
+
+    // Returns the intended audience of the token, if it has one.
+    fn aud(&self) -> Option<&str> {
+        None
+    }
+
+    // Returns whether `aud` is one of the token's intended audiences.
+    //
+    // Called with each expected audience when `Validation::expected_aud` is
+    // set. By default this compares `aud` against the single audience
+    // returned by `aud()`; payloads with several audiences should override
+    // it.
+    fn has_aud(&self, aud: &str) -> bool {
+        self.aud() == Some(aud)
+    }
+
+    // Returns the version the token was issued at, if it has one.
+    //
+    // Only checked when `Validation::min_version` is set, in which case
+    // tokens without a version are rejected.
+    fn version(&self) -> Option<u64> {
+        None
+    }
 }
 
 // Creates a token from the provided payload and secret.
-// 
-// This function serializes the payload, signs it with the secret, 
-// and returns a JWT-like string consisting of a base64url-encoded payload 
-// and a base64url-encoded signature. The token is used for authentication 
-// and authorization purposes.
+//
+// This function serializes the payload, signs it with the secret,
+// and returns a JWT-like string consisting of a base64url-encoded header,
+// a base64url-encoded payload and a base64url-encoded signature. The token
+// is signed with HMAC-SHA256 and is used for authentication and
+// authorization purposes.
 //
 // # Arguments
 //
 // * `payload` - The data to be serialized into the token.
-// * `secret` - The secret key used to sign the token.
+// * `secret` - The secret key used to sign the token, as a string or raw bytes.
 //
 // # Returns
 //
-// * `Ok(String)` - The generated token string.
-// * `Err(Box<dyn Error>)` - Any error that occurs during the token creation process.
-pub fn create_token<T>(payload: &T, secret: &str) -> Result<String, Box<dyn Error>>
+// * `Ok(Token)` - The generated token.
+// * `Err(TokenError)` - Any error that occurs during the token creation process.
+pub fn create_token<T>(payload: &T, secret: impl AsRef<[u8]>) -> Result<Token, TokenError>
 where
     T: Serialize,
 {
-    let payload_bytes = to_vec(payload)?;
-    let signature = sign_payload(secret, &payload_bytes)?;
+    create_token_with_alg(payload, secret, Algorithm::Hs256)
+}
 
-    Ok(format!(
-        "{}.{}",
-        URL_SAFE_NO_PAD.encode(&payload_bytes),
-        URL_SAFE_NO_PAD.encode(&signature)
-    ))
+// Creates a token whose signature also covers associated data.
+//
+// The signature is computed over the token and `aad`, but `aad` itself is
+// not put in the token. Binding a token to data from its context, such as
+// the client's IP address or a TLS channel binding, means a stolen token
+// fails to verify anywhere else. Verify it with `verify_token_with_aad`
+// and the same data; `verify_token` rejects it.
+//
+// # Arguments
+//
+// * `payload` - The data to be serialized into the token.
+// * `secret` - The secret key used to sign the token, as a string or raw bytes.
+// * `aad` - The data the signature is bound to.
+//
+// # Returns
+//
+// * `Ok(Token)` - The generated token.
+// * `Err(TokenError)` - Any error that occurs during the token creation process.
+pub fn create_token_with_aad<T>(
+    payload: &T,
+    secret: impl AsRef<[u8]>,
+    aad: &[u8],
+) -> Result<Token, TokenError>
+where
+    T: Serialize,
+{
+    let options = TokenOptions::default();
+    encode_token(payload, &options, |signing_input| {
+        compute_signature_with_alg(secret.as_ref(), &with_aad(signing_input, aad), options.alg)
+    })
 }
 
-// Signs the payload using the provided secret.
+// Creates a token from the provided payload and secret using the given algorithm.
+//
+// The algorithm is recorded in the token header so `verify_token` can
+// check the signature with the matching HMAC implementation.
 //
 // # Arguments
 //
-// * `secret` - The secret key used to sign the payload.
-// * `payload` - The payload data to be signed.
+// * `payload` - The data to be serialized into the token.
+// * `secret` - The secret key used to sign the token, as a string or raw bytes.
+// * `alg` - The HMAC algorithm used to sign the token.
 //
 // # Returns
 //
-// * `Ok(Vec<u8>)` - The generated signature.
-// * `Err(Box<dyn Error>)` - Any error that occurs during the signing process.
-fn sign_payload(secret: &str, payload: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
-    let mut mac = HmacSha256::new_from_slice(secret.as_bytes())?;
-    mac.update(payload);
-    Ok(mac.finalize().into_bytes().to_vec())
+// * `Ok(Token)` - The generated token.
+// * `Err(TokenError)` - Any error that occurs during the token creation process.
+pub fn create_token_with_alg<T>(
+    payload: &T,
+    secret: impl AsRef<[u8]>,
+    alg: Algorithm,
+) -> Result<Token, TokenError>
+where
+    T: Serialize,
+{
+    let options = TokenOptions {
+        alg,
+        ..TokenOptions::default()
+    };
+    create_token_with_options(payload, secret, &options)
 }
 
-// Verifies a token and returns the decoded payload if valid.
+// Creates a token from the provided payload and secret tagged with a token type.
 //
-// This function decodes the token, verifies the signature, checks if the 
-// token is expired, and returns the payload if everything is valid. 
-// The payload is deserialized into the type `T`.
+// The type is recorded in the token header. A service that mints several
+// kinds of token, such as access and refresh tokens, can set
+// `Validation::expected_typ` so one kind is never accepted in place of
+// another.
 //
 // # Arguments
 //
-// * `secret` - The secret key used to verify the token's signature.
-// * `token` - The token string to be verified and decoded.
+// * `payload` - The data to be serialized into the token.
+// * `secret` - The secret key used to sign the token, as a string or raw bytes.
+// * `typ` - The type of the token, for example `"access"`.
 //
 // # Returns
 //
-// * `Ok(T)` - The deserialized payload if the token is valid and not expired.
-// * `Err(Box<dyn Error>)` - Any error that occurs during the verification process.
-pub fn verify_token<T>(secret: &str, token: &str) -> Result<T, Box<dyn Error>>
+// * `Ok(Token)` - The generated token.
+// * `Err(TokenError)` - Any error that occurs during the token creation process.
+pub fn create_typed_token<T>(
+    payload: &T,
+    secret: impl AsRef<[u8]>,
+    typ: &str,
+) -> Result<Token, TokenError>
 where
-    T: for<'de> Deserialize<'de> + Expirable,
+    T: Serialize,
 {
-    let parts: Vec<&str> = token.split('.').collect();
-    if parts.len() != 2 {
-        return Err(Box::new(TokenError("Invalid token format".to_string())));
-    }
+    let options = TokenOptions {
+        typ: Some(typ.to_string()),
+        ..TokenOptions::default()
+    };
+    create_token_with_options(payload, secret, &options)
+}
+
+// Creates a token from the provided payload and secret using the given options.
+//
+// The algorithm and payload format are recorded in the token header so
+// `verify_token` and `decode_token` can handle the token without being
+// told how it was created.
+//
+// # Arguments
+//
+// * `payload` - The data to be serialized into the token.
+// * `secret` - The secret key used to sign the token, as a string or raw bytes.
+// * `options` - The algorithm and payload format to use.
+//
+// # Returns
+//
+// * `Ok(Token)` - The generated token.
+// * `Err(TokenError)` - Any error that occurs during the token creation process.
+pub fn create_token_with_options<T>(
+    payload: &T,
+    secret: impl AsRef<[u8]>,
+    options: &TokenOptions,
+) -> Result<Token, TokenError>
+where
+    T: Serialize,
+{
+    encode_token(payload, options, |signing_input| {
+        compute_signature_with_alg(secret.as_ref(), signing_input, options.alg)
+    })
+}
 
-    let payload_bytes = URL_SAFE_NO_PAD.decode(parts[0])?;
-    let signature = URL_SAFE_NO_PAD.decode(parts[1])?;
+// Creates a token that expires after the given time to live.
+//
+// The payload's `exp` is set to now plus `ttl` before the token is
+// created, so callers can't forget to set it. The new expiration is left
+// on the payload, where the caller can read it. Payloads that manage
+// `exp` themselves can keep using `create_token`.
+//
+// # Arguments
+//
+// * `payload` - The data to be serialized into the token.
+// * `secret` - The secret key used to sign the token, as a string or raw bytes.
+// * `ttl` - How long the token is valid for.
+//
+// # Returns
+//
+// * `Ok(Token)` - The generated token.
+// * `Err(TokenError)` - Any error that occurs during the token creation process.
+#[cfg(feature = "std")]
+pub fn create_token_with_ttl<T>(
+    payload: &mut T,
+    secret: impl AsRef<[u8]>,
+    ttl: Duration,
+) -> Result<Token, TokenError>
+where
+    T: Serialize + SetExpiry,
+{
+    let now = SystemClock.now();
+    payload.set_exp(now.saturating_add(duration_secs(ttl)));
+    create_token(payload, secret)
+}
 
-    let expected_signature = sign_payload(secret, &payload_bytes)?;
-    if signature != expected_signature {
-        return Err(Box::new(TokenError("Invalid token signature".to_string())));
+// Creates a token that expires at the given instant.
+//
+// This is `create_token_with_ttl` for an absolute expiry, such as the end
+// of the day. The payload's `exp` is set to `expiry`, in whole seconds, and
+// left on the payload. An expiry that is not in the future would produce a
+// token that is already expired, so it is rejected with `Expired`.
+//
+// # Arguments
+//
+// * `payload` - The data to be serialized into the token.
+// * `secret` - The secret key used to sign the token, as a string or raw bytes.
+// * `expiry` - When the token expires.
+//
+// # Returns
+//
+// * `Ok(Token)` - The generated token.
+// * `Err(TokenError)` - `Expired` if `expiry` has passed, or any error that occurs during the
+//   token creation process.
+#[cfg(all(feature = "std", feature = "chrono"))]
+pub fn create_token_until<T>(
+    payload: &mut T,
+    secret: impl AsRef<[u8]>,
+    expiry: DateTime<Utc>,
+) -> Result<Token, TokenError>
+where
+    T: Serialize + SetExpiry,
+{
+    let exp = expiry.timestamp();
+    // Tokens are expired from the second `now == exp` onwards.
+    if exp <= SystemClock.now() {
+        return Err(TokenErrorKind::Expired.into());
     }
+    payload.set_exp(exp);
+    create_token(payload, secret)
+}
 
-    let payload: T = from_slice(&payload_bytes)?;
+// Reissues a valid token with its expiration pushed back.
+//
+// The token is verified as in `verify_token`, its `exp` is set to now
+// plus `new_ttl`, and it is signed again with the same algorithm, format,
+// key id and type. This slides a session forward while the user is
+// active. Everything else in the payload is kept, as long as `T` holds it:
+// claims that `T` does not have are dropped, so deserialize into a type
+// such as `Claims` that keeps unknown claims.
+//
+// # Arguments
+//
+// * `secret` - The secret key used to verify and sign the token, as a string or raw bytes.
+// * `token` - The still-valid token to reissue.
+// * `new_ttl` - How long the reissued token is valid for, from now.
+//
+// # Returns
+//
+// * `Ok(Token)` - The reissued token.
+// * `Err(TokenError)` - The token is not valid, or any error that occurs while reissuing it.
+#[cfg(feature = "std")]
+pub fn refresh_expiry<T>(
+    secret: impl AsRef<[u8]>,
+    token: &str,
+    new_ttl: Duration,
+) -> Result<Token, TokenError>
+where
+    T: Serialize + for<'de> Deserialize<'de> + Expirable + SetExpiry,
+{
+    let secret = secret.as_ref();
+    let mut payload: T = verify_token(secret, token)?;
+    // The header can be trusted now that the token has been verified.
+    let header = decode_header(token)?;
 
-    let exp_timestamp = payload.exp();
-    let now_timestamp = Utc::now().timestamp();
+    let options = TokenOptions {
+        alg: header.alg,
+        format: header.fmt,
+        kid: header.kid,
+        typ: header.typ,
+        ..TokenOptions::default()
+    };
+    payload.set_exp(SystemClock.now().saturating_add(duration_secs(new_ttl)));
+    create_token_with_options(&payload, secret, &options)
+}
 
-    if exp_timestamp < now_timestamp {
-        return Err(Box::new(TokenError("Token has expired".to_string())));
-    }
+// Reissues a valid token signed with a new secret.
+//
+// The token is verified with `old_secret` as in `verify_token`, with `T`
+// used to check its claims, and its payload is then signed with
+// `new_secret` using the same algorithm, format, key id and type. The
+// payload is carried over byte for byte, so the claims are unchanged
+// whatever `T` holds. This moves long-lived tokens over to a new secret
+// during a migration without logging anyone out. Expired tokens are not
+// reissued.
+//
+// # Arguments
+//
+// * `old_secret` - The secret key the token was signed with, as a string or raw bytes.
+// * `new_secret` - The secret key used to sign the reissued token, as a string or raw bytes.
+// * `token` - The still-valid token to reissue.
+//
+// # Returns
+//
+// * `Ok(Token)` - The reissued token.
+// * `Err(TokenError)` - The token is not valid, or any error that occurs while reissuing it.
+#[cfg(feature = "std")]
+pub fn reissue<T>(
+    old_secret: impl AsRef<[u8]>,
+    new_secret: impl AsRef<[u8]>,
+    token: &str,
+) -> Result<Token, TokenError>
+where
+    T: for<'de> Deserialize<'de> + Expirable,
+{
+    let validation = Validation::default();
+    let (header, payload_bytes) = verified_payload(&[old_secret], token, &validation)?;
+    let (payload, failures): (T, _) =
+        deserialize_required(header.fmt, &payload_bytes, &validation)?;
+    validate_claims(&payload, &validation, now_millis(), failures)?;
 
-    Ok(payload)
+    let options = TokenOptions {
+        alg: header.alg,
+        format: header.fmt,
+        kid: header.kid,
+        typ: header.typ,
+        ..TokenOptions::default()
+    };
+    encode_segments(&payload_bytes, &options, |signing_input| {
+        compute_signature_with_alg(new_secret.as_ref(), signing_input, options.alg)
+    })
 }
 
-// Decodes a token and returns the payload if valid.
+// Creates a token carrying the payload bytes as they are.
 //
-// This function decodes the token and deserializes the payload into the type `T`.
-// It does not check for signature or expiration, making it suitable for use cases 
-// where only the payload is needed.
+// The payload is not serialized, so any encoding the caller already uses,
+// such as protobuf, can be signed without going through serde. The header
+// marks the payload as raw, so the token can only be read back with
+// `verify_token_raw`. No claims are checked for raw payloads, including
+// the expiration: put an expiry in the payload and check it yourself if
+// the token should expire.
 //
 // # Arguments
 //
-// * `token` - The token string to be decoded.
+// * `payload` - The bytes to be carried by the token.
+// * `secret` - The secret key used to sign the token, as a string or raw bytes.
 //
 // # Returns
 //
-// * `Ok(T)` - The deserialized payload if the token is valid.
-// * `Err(Box<dyn Error>)` - Any error that occurs during the decoding process.
-pub fn decode_token<T>(token: &str) -> Result<T, Box<dyn Error>>
+// * `Ok(Token)` - The generated token.
+// * `Err(TokenError)` - Any error that occurs during the token creation process.
+pub fn create_token_raw(payload: &[u8], secret: impl AsRef<[u8]>) -> Result<Token, TokenError> {
+    let options = TokenOptions {
+        format: Format::Raw,
+        ..TokenOptions::default()
+    };
+    encode_segments(payload, &options, |signing_input| {
+        compute_signature_with_alg(secret.as_ref(), signing_input, options.alg)
+    })
+}
+
+// Builds a token for the payload, signing it with `sign`.
+fn encode_token<T, F>(payload: &T, options: &TokenOptions, sign: F) -> Result<Token, TokenError>
 where
-    T: for<'de> Deserialize<'de>,
+    T: Serialize,
+    F: FnOnce(&[u8]) -> Result<Vec<u8>, TokenError>,
 {
-    let parts: Vec<&str> = token.split('.').collect();
-    if parts.len() != 2 {
-        return Err(Box::new(TokenError("Invalid token format".to_string())));
-    }
+    let payload_bytes = serialize_options_payload(options, payload)?;
+    encode_segments(&payload_bytes, options, sign)
+}
+
+// Builds a token for the already serialized payload, signing it with `sign`.
+fn encode_segments<F>(
+    payload_bytes: &[u8],
+    options: &TokenOptions,
+    sign: F,
+) -> Result<Token, TokenError>
+where
+    F: FnOnce(&[u8]) -> Result<Vec<u8>, TokenError>,
+{
+    let header_bytes = serialize_header(options)?;
 
-    let payload_bytes = URL_SAFE_NO_PAD.decode(parts[0])?;
-    let payload: T = from_slice(&payload_bytes)?;
+    let engine = options.encoding.encoder();
+    let signing_input = format!(
+        "{}.{}",
+        engine.encode(&header_bytes),
+        engine.encode(payload_bytes)
+    );
+    let signature = sign(signing_input.as_bytes())?;
 
-    Ok(payload)
-}
\ No newline at end of file
+    Ok(Token::new(format!("{}.{}", signing_input, engine.encode(&signature))))
+}
+
+// Writes a token for the payload straight into `writer`.
+//
+// This produces the same token as `create_token`, but the base64url
+// segments are encoded directly into the writer and signed as they are
+// written, without building the token as a `String` first. This is useful
+// when streaming many tokens into a response body or a file.
+//
+// # Arguments
+//
+// * `payload` - The data to be serialized into the token.
+// * `secret` - The secret key used to sign the token, as a string or raw bytes.
+// * `writer` - Where the token is written.
+//
+// # Returns
+//
+// * `Ok(())` - The token was written.
+// * `Err(TokenError)` - Any error that occurs while creating or writing the token.
+#[cfg(feature = "std")]
+pub fn write_token<T, W>(
+    payload: &T,
+    secret: impl AsRef<[u8]>,
+    writer: &mut W,
+) -> Result<(), TokenError>
+where
+    T: Serialize,
+    W: io::Write + ?Sized,
+{
+    write_token_with_options(payload, secret, &TokenOptions::default(), writer)
+}
+
+// Writes a token for the payload straight into `writer` using the given options.
+//
+// This is `write_token` producing the same token as `create_token_with_options`.
+//
+// # Arguments
+//
+// * `payload` - The data to be serialized into the token.
+// * `secret` - The secret key used to sign the token, as a string or raw bytes.
+// * `options` - The algorithm and payload format to use.
+// * `writer` - Where the token is written.
+//
+// # Returns
+//
+// * `Ok(())` - The token was written.
+// * `Err(TokenError)` - Any error that occurs while creating or writing the token.
+#[cfg(feature = "std")]
+pub fn write_token_with_options<T, W>(
+    payload: &T,
+    secret: impl AsRef<[u8]>,
+    options: &TokenOptions,
+    writer: &mut W,
+) -> Result<(), TokenError>
+where
+    T: Serialize,
+    W: io::Write + ?Sized,
+{
+    let header_bytes = serialize_header(options)?;
+    let payload_bytes = serialize_options_payload(options, payload)?;
+    let segments = [header_bytes.as_slice(), payload_bytes.as_slice()];
+    let engine = options.encoding.encoder();
+    let secret = secret.as_ref();
+    check_secret(secret)?;
+
+    match options.alg {
+        Algorithm::Hs256 => write_signed::<HmacSha256, W>(secret, segments, engine, writer),
+        Algorithm::Hs384 => write_signed::<HmacSha384, W>(secret, segments, engine, writer),
+        Algorithm::Hs512 => write_signed::<HmacSha512, W>(secret, segments, engine, writer),
+    }
+}
+
+// Writes the encoded header and payload, signing the text as it is
+// written, followed by the encoded signature.
+#[cfg(feature = "std")]
+fn write_signed<M, W>(
+    secret: &[u8],
+    [header_bytes, payload_bytes]: [&[u8]; 2],
+    engine: &GeneralPurpose,
+    writer: &mut W,
+) -> Result<(), TokenError>
+where
+    M: Mac + KeyInit,
+    W: io::Write + ?Sized,
+{
+    let mut signed = MacWriter {
+        mac: <M as Mac>::new_from_slice(secret)?,
+        writer,
+    };
+    write_segment(&mut signed, header_bytes, engine)?;
+    io::Write::write_all(&mut signed, b".")?;
+    write_segment(&mut signed, payload_bytes, engine)?;
+
+    let MacWriter { mac, writer } = signed;
+    writer.write_all(b".")?;
+    write_segment(writer, &mac.finalize().into_bytes(), engine)?;
+    Ok(())
+}
+
+// Base64url-encodes the bytes into the writer, a chunk at a time.
+#[cfg(feature = "std")]
+fn write_segment<W>(writer: &mut W, bytes: &[u8], engine: &GeneralPurpose) -> io::Result<()>
+where
+    W: io::Write + ?Sized,
+{
+    // Every whole chunk is a multiple of 3 bytes, so the chunks encode to
+    // the same text as the segment encoded in one go, with any padding
+    // only after the last chunk.
+    let mut encoded = [0; 1024];
+    for chunk in bytes.chunks(768) {
+        let len = engine
+            .encode_slice(chunk, &mut encoded)
+            .map_err(io::Error::other)?;
+        writer.write_all(&encoded[..len])?;
+    }
+    Ok(())
+}
+
+// A writer that feeds everything written through it into a MAC.
+#[cfg(feature = "std")]
+struct MacWriter<'w, M, W: ?Sized> {
+    mac: M,
+    writer: &'w mut W,
+}
+
+#[cfg(feature = "std")]
+impl<M, W> io::Write for MacWriter<'_, M, W>
+where
+    M: Mac,
+    W: io::Write + ?Sized,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.writer.write(buf)?;
+        // Only what was actually written is signed. A writer claiming more
+        // than it was given is broken, and would otherwise cause a panic.
+        let written_bytes = buf
+            .get(..written)
+            .ok_or_else(|| io::Error::other("writer reported more bytes than it was given"))?;
+        self.mac.update(written_bytes);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+// Serializes the header recording the options a token is created with.
+fn serialize_header(options: &TokenOptions) -> Result<Vec<u8>, TokenError> {
+    let header = Header {
+        ver: TOKEN_VERSION,
+        alg: options.alg,
+        fmt: options.format,
+        kid: options.kid.clone(),
+        typ: options.typ.clone(),
+    };
+    Ok(header::write(&header))
+}
+
+// Serializes the payload with the given format.
+fn serialize_payload<T>(format: Format, payload: &T) -> Result<Vec<u8>, TokenError>
+where
+    T: Serialize,
+{
+    match format {
+        #[cfg(feature = "msgpack")]
+        Format::MessagePack => Ok(to_vec(payload)?),
+        #[cfg(not(feature = "msgpack"))]
+        Format::MessagePack => Err(TokenErrorKind::Serialize(MSGPACK_DISABLED.to_string()).into()),
+        Format::Json => serde_json::to_vec(payload).map_err(|err| {
+            caused_by(TokenErrorKind::Serialize("Could not encode JSON".to_string()), err)
+        }),
+        Format::Raw => Err(TokenErrorKind::Serialize(
+            "Raw payloads must be created with create_token_raw".to_string(),
+        )
+        .into()),
+    }
+}
+
+// Serializes the payload as configured by the token options.
+fn serialize_options_payload<T>(options: &TokenOptions, payload: &T) -> Result<Vec<u8>, TokenError>
+where
+    T: Serialize,
+{
+    if options.canonical {
+        let value = serde_json::to_value(payload).map_err(|err| {
+            caused_by(TokenErrorKind::Serialize("Could not canonicalize payload".to_string()), err)
+        })?;
+        return serialize_payload(options.format, &Canonical(&value));
+    }
+    #[cfg(feature = "msgpack")]
+    if options.named_fields && options.format == Format::MessagePack {
+        return Ok(to_vec_named(payload)?);
+    }
+    serialize_payload(options.format, payload)
+}
+
+// Deserializes the payload with the given format, allowing at most
+// `max_depth` nested arrays and maps.
+fn deserialize_payload<T>(
+    format: Format,
+    payload_bytes: &[u8],
+    max_depth: usize,
+) -> Result<T, TokenError>
+where
+    T: for<'de> Deserialize<'de>,
+{
+    match format {
+        Format::MessagePack => msgpack_from_slice(payload_bytes, max_depth),
+        Format::Json => {
+            check_json_depth(payload_bytes, max_depth)?;
+            serde_json::from_slice(payload_bytes).map_err(|err| {
+                payload_error(TokenErrorKind::Decode("Invalid JSON data".to_string()), err)
+            })
+        }
+        Format::Raw => Err(TokenErrorKind::Decode(
+            "Raw payloads must be read with verify_token_raw".to_string(),
+        )
+        .into()),
+    }
+}
+
+// Decodes MessagePack, allowing at most `max_depth` nested arrays and maps.
+#[cfg(feature = "msgpack")]
+fn msgpack_from_slice<T>(bytes: &[u8], max_depth: usize) -> Result<T, TokenError>
+where
+    T: for<'de> Deserialize<'de>,
+{
+    let mut deserializer = rmp_serde::Deserializer::from_read_ref(bytes);
+    // rmp_serde counts the limit itself as a level.
+    deserializer.set_max_depth(max_depth.saturating_add(1));
+    T::deserialize(&mut deserializer).map_err(|err| {
+        // A payload that is a single value can't be what a struct or a
+        // collection expects, which rmp_serde doesn't make obvious.
+        match (shape::msgpack_scalar(bytes), shape::expected_container::<T>()) {
+            (Some(found), Some(expected)) => {
+                payload_error(TokenErrorKind::PayloadTypeMismatch { expected, found }, err)
+            }
+            _ => err.into(),
+        }
+    })
+}
+
+// Without the `msgpack` feature MessagePack payloads can't be decoded;
+// tokens for `no_std` targets should be created with `Format::Json`.
+#[cfg(not(feature = "msgpack"))]
+fn msgpack_from_slice<T>(_bytes: &[u8], _max_depth: usize) -> Result<T, TokenError>
+where
+    T: for<'de> Deserialize<'de>,
+{
+    Err(TokenErrorKind::Decode(MSGPACK_DISABLED.to_string()).into())
+}
+
+// The error for MessagePack payloads without the `msgpack` feature.
+#[cfg(not(feature = "msgpack"))]
+const MSGPACK_DISABLED: &str = "MessagePack payloads need the msgpack feature";
+
+// Checks that JSON nests at most `max_depth` arrays and objects.
+//
+// serde_json only applies its own fixed limit of 128 levels, so this scans
+// the bytes before they are deserialized. Malformed JSON is left for
+// serde_json to reject.
+fn check_json_depth(bytes: &[u8], max_depth: usize) -> Result<(), TokenError> {
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for &byte in bytes {
+        if in_string {
+            if escaped {
+                escaped = false;
+            } else if byte == b'\\' {
+                escaped = true;
+            } else if byte == b'"' {
+                in_string = false;
+            }
+            continue;
+        }
+
+        match byte {
+            b'"' => in_string = true,
+            b'[' | b'{' => {
+                depth += 1;
+                if depth > max_depth {
+                    return Err(TokenErrorKind::TooDeep.into());
+                }
+            }
+            b']' | b'}' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+// Computes the HMAC-SHA256 signature of the payload bytes.
+//
+// This is the signing step `create_token` uses, exposed so tooling can
+// reconstruct the expected signature of a token, or check its own signer,
+// without reimplementing HMAC. To get a token's signature, pass the text
+// it covers: the header and payload segments joined by a `.`.
+//
+// # Arguments
+//
+// * `secret` - The secret key used to sign the payload, as a string or raw bytes.
+// * `payload` - The payload data to be signed.
+//
+// # Returns
+//
+// * `Ok(Vec<u8>)` - The generated signature.
+// * `Err(TokenError)` - Any error that occurs during the signing process.
+pub fn compute_signature(secret: impl AsRef<[u8]>, payload: &[u8]) -> Result<Vec<u8>, TokenError> {
+    compute_signature_with_alg(secret, payload, Algorithm::Hs256)
+}
+
+// Computes the signature of the payload bytes using the given algorithm.
+//
+// # Arguments
+//
+// * `secret` - The secret key used to sign the payload, as a string or raw bytes.
+// * `payload` - The payload data to be signed.
+// * `alg` - The HMAC algorithm used to sign the payload.
+//
+// # Returns
+//
+// * `Ok(Vec<u8>)` - The generated signature.
+// * `Err(TokenError)` - Any error that occurs during the signing process.
+pub fn compute_signature_with_alg(
+    secret: impl AsRef<[u8]>,
+    payload: &[u8],
+    alg: Algorithm,
+) -> Result<Vec<u8>, TokenError> {
+    let secret = secret.as_ref();
+    check_secret(secret)?;
+    match alg {
+        Algorithm::Hs256 => hmac_sign::<HmacSha256>(secret, payload),
+        Algorithm::Hs384 => hmac_sign::<HmacSha384>(secret, payload),
+        Algorithm::Hs512 => hmac_sign::<HmacSha512>(secret, payload),
+    }
+}
+
+fn hmac_sign<M>(secret: &[u8], payload: &[u8]) -> Result<Vec<u8>, TokenError>
+where
+    M: Mac + KeyInit,
+{
+    let mut mac = <M as Mac>::new_from_slice(secret)?;
+    mac.update(payload);
+    Ok(mac.finalize().into_bytes().to_vec())
+}
+
+// Checks the signature of the payload using the provided secret.
+//
+// The comparison is done by `Mac::verify_slice`, which runs in constant
+// time so the time taken does not reveal how many bytes matched.
+//
+// # Arguments
+//
+// * `alg` - The HMAC algorithm the payload was signed with.
+// * `secret` - The secret key used to sign the payload.
+// * `payload` - The payload data that was signed.
+// * `signature` - The signature to check.
+//
+// # Returns
+//
+// * `Ok(())` - The signature is valid.
+// * `Err(TokenError)` - The signature does not match or the key is invalid.
+fn verify_signature(
+    alg: Algorithm,
+    secret: &[u8],
+    payload: &[u8],
+    signature: &[u8],
+) -> Result<(), TokenError> {
+    check_secret(secret)?;
+    match alg {
+        Algorithm::Hs256 => hmac_verify::<HmacSha256>(secret, payload, signature),
+        Algorithm::Hs384 => hmac_verify::<HmacSha384>(secret, payload, signature),
+        Algorithm::Hs512 => hmac_verify::<HmacSha512>(secret, payload, signature),
+    }
+}
+
+fn hmac_verify<M>(secret: &[u8], payload: &[u8], signature: &[u8]) -> Result<(), TokenError>
+where
+    M: Mac + KeyInit,
+{
+    let mut mac = <M as Mac>::new_from_slice(secret)?;
+    mac.update(payload);
+    mac.verify_slice(signature)
+        .map_err(|_| TokenErrorKind::InvalidSignature.into())
+}
+
+// Signs content sent separately from its signature.
+//
+// Only the signature is returned, base64url-encoded, so content that
+// travels on its own, such as a request body, is not copied into a token.
+// This is the detached-content mode of JWS. The content is signed with
+// HMAC-SHA256 and can be checked with `verify_detached`; it has no claims,
+// so nothing expires. Use `StreamSigner` for content too large to hold in
+// memory.
+//
+// # Arguments
+//
+// * `payload` - The content to be signed.
+// * `secret` - The secret key used to sign the content, as a string or raw bytes.
+//
+// # Returns
+//
+// * `Ok(String)` - The base64url-encoded signature.
+// * `Err(TokenError)` - Any error that occurs during the signing process.
+pub fn sign_detached(payload: &[u8], secret: impl AsRef<[u8]>) -> Result<String, TokenError> {
+    let mut signer = StreamSigner::new(secret)?;
+    signer.update(payload);
+    Ok(signer.finalize())
+}
+
+// Checks a signature created by `sign_detached` for the content.
+//
+// # Arguments
+//
+// * `payload` - The content the signature was sent with.
+// * `signature` - The base64url-encoded signature, with or without padding.
+// * `secret` - The secret key used to sign the content, as a string or raw bytes.
+//
+// # Returns
+//
+// * `Ok(())` - The signature is valid for the content.
+// * `Err(TokenError)` - The signature does not match, or any error that occurs while checking
+//   it.
+pub fn verify_detached(
+    payload: &[u8],
+    signature: &str,
+    secret: impl AsRef<[u8]>,
+) -> Result<(), TokenError> {
+    let mut verifier = StreamVerifier::new(secret)?;
+    verifier.update(payload);
+    verifier.verify(signature)
+}
+
+// Verifies a token and returns the decoded payload if valid.
+//
+// This function decodes the token, verifies the signature with the
+// algorithm recorded in its header, checks if the token is expired or not
+// yet valid, and returns the payload if everything is valid. The payload
+// is deserialized into the type `T`.
+//
+// # Arguments
+//
+// * `secret` - The secret key used to verify the token's signature, as a string or raw bytes.
+// * `token` - The token string to be verified and decoded.
+//
+// # Returns
+//
+// * `Ok(T)` - The deserialized payload if the token is valid and not expired.
+// * `Err(TokenError)` - Any error that occurs during the verification process.
+#[cfg(feature = "std")]
+pub fn verify_token<T>(secret: impl AsRef<[u8]>, token: &str) -> Result<T, TokenError>
+where
+    T: for<'de> Deserialize<'de> + Expirable,
+{
+    verify(secret, token, &Validation::default())
+}
+
+// Verifies a token that has already been split into its segments.
+//
+// This is `verify_token` for middleware that holds the header, payload and
+// signature segments separately, for example because it decoded the
+// header earlier with `decode_header`. The token is checked and its errors
+// reported exactly as in `verify_token`. All three segments are needed,
+// since the signature covers the header as well as the payload.
+//
+// # Arguments
+//
+// * `secret` - The secret key used to verify the token's signature, as a string or raw bytes.
+// * `header` - The header segment of the token.
+// * `payload` - The payload segment of the token.
+// * `signature` - The signature segment of the token.
+//
+// # Returns
+//
+// * `Ok(T)` - The deserialized payload if the token is valid and not expired.
+// * `Err(TokenError)` - Any error that occurs during the verification process.
+#[cfg(feature = "std")]
+pub fn verify_parts<T>(
+    secret: impl AsRef<[u8]>,
+    header: &str,
+    payload: &str,
+    signature: &str,
+) -> Result<T, TokenError>
+where
+    T: for<'de> Deserialize<'de> + Expirable,
+{
+    let validation = Validation::default();
+    // The length the joined token would have.
+    if header.len() + payload.len() + signature.len() + 2 > validation.max_token_len {
+        return Err(TokenErrorKind::TooLarge.into());
+    }
+    if [header, payload, signature]
+        .iter()
+        .any(|segment| segment.is_empty() || segment.contains('.'))
+    {
+        return Err(TokenErrorKind::InvalidFormat.into());
+    }
+
+    let signing_input = format!("{}.{}", header, payload);
+    let segments = (header, payload, signature);
+    let (header, payload_bytes) =
+        verified_segments(&[secret], segments, signing_input.as_bytes(), &validation)?;
+    let (payload, failures) = deserialize_required(header.fmt, &payload_bytes, &validation)?;
+    validate_claims(&payload, &validation, now_millis(), failures)?;
+    Ok(payload)
+}
+
+// Verifies a token created with `create_token_with_aad` and returns the decoded payload if valid.
+//
+// The token is checked as in `verify_token`, except that its signature
+// must cover `aad` as well. A token presented with different data fails
+// with `InvalidSignature`.
+//
+// # Arguments
+//
+// * `secret` - The secret key used to verify the token's signature, as a string or raw bytes.
+// * `token` - The token string to be verified and decoded.
+// * `aad` - The data the token's signature must be bound to.
+//
+// # Returns
+//
+// * `Ok(T)` - The deserialized payload if the token is valid and not expired.
+// * `Err(TokenError)` - Any error that occurs during the verification process.
+#[cfg(feature = "std")]
+pub fn verify_token_with_aad<T>(
+    secret: impl AsRef<[u8]>,
+    token: &str,
+    aad: &[u8],
+) -> Result<T, TokenError>
+where
+    T: for<'de> Deserialize<'de> + Expirable,
+{
+    let verifier = WithAad {
+        verifier: &[secret],
+        aad,
+    };
+    verify_token_inner(&verifier, token, &Validation::default(), now_millis())
+}
+
+// Verifies a token signed with the expected algorithm and returns the decoded payload if valid.
+//
+// This behaves like `verify_token`, but rejects tokens whose header
+// declares a different algorithm than `alg`.
+//
+// # Arguments
+//
+// * `secret` - The secret key used to verify the token's signature, as a string or raw bytes.
+// * `token` - The token string to be verified and decoded.
+// * `alg` - The algorithm the token is expected to be signed with.
+//
+// # Returns
+//
+// * `Ok(T)` - The deserialized payload if the token is valid and not expired.
+// * `Err(TokenError)` - Any error that occurs during the verification process.
+#[cfg(feature = "std")]
+pub fn verify_token_with_alg<T>(
+    secret: impl AsRef<[u8]>,
+    token: &str,
+    alg: Algorithm,
+) -> Result<T, TokenError>
+where
+    T: for<'de> Deserialize<'de> + Expirable,
+{
+    let validation = Validation {
+        algorithms: vec![alg],
+        ..Validation::default()
+    };
+    verify(secret, token, &validation)
+}
+
+// Verifies a token like `verify_token`, allowing for clock skew.
+//
+// The token is still accepted for up to `leeway` after its expiration
+// time (and up to `leeway` before its "not before" time), which covers
+// clocks drifting apart between the node that signed the token and the
+// node verifying it. A zero leeway behaves exactly like `verify_token`.
+//
+// # Arguments
+//
+// * `secret` - The secret key used to verify the token's signature, as a string or raw bytes.
+// * `token` - The token string to be verified and decoded.
+// * `leeway` - How long past its expiration the token is still accepted.
+//
+// # Returns
+//
+// * `Ok(T)` - The deserialized payload if the token is valid and not expired.
+// * `Err(TokenError)` - Any error that occurs during the verification process.
+#[cfg(feature = "std")]
+pub fn verify_token_with_leeway<T>(
+    secret: impl AsRef<[u8]>,
+    token: &str,
+    leeway: Duration,
+) -> Result<T, TokenError>
+where
+    T: for<'de> Deserialize<'de> + Expirable,
+{
+    let validation = Validation {
+        leeway,
+        ..Validation::default()
+    };
+    verify(secret, token, &validation)
+}
+
+// Verifies a token and checks it against the provided validation.
+//
+// This is the single entry point for every check the crate can make:
+// besides the signature, `exp` and `nbf` checks done by `verify_token`,
+// the validation can restrict the accepted algorithms, allow for clock
+// skew, cap the token's age, and require the token to come from a trusted
+// issuer and be intended for this service, which prevents a token minted
+// for one service from being replayed against another. A default
+// `Validation` behaves exactly like `verify_token`.
+//
+// # Arguments
+//
+// * `secret` - The secret key used to verify the token's signature, as a string or raw bytes.
+// * `token` - The token string to be verified and decoded.
+// * `validation` - The checks to run on the token.
+//
+// # Returns
+//
+// * `Ok(T)` - The deserialized payload if the token is valid.
+// * `Err(TokenError)` - Any error that occurs during the verification process.
+#[cfg(feature = "std")]
+pub fn verify<T>(
+    secret: impl AsRef<[u8]>,
+    token: &str,
+    validation: &Validation,
+) -> Result<T, TokenError>
+where
+    T: for<'de> Deserialize<'de> + Expirable,
+{
+    verify_token_inner(&[secret], token, validation, now_millis())
+}
+
+// Verifies a token and checks its claims against the provided validation.
+//
+// This is the same as `verify`.
+//
+// # Arguments
+//
+// * `secret` - The secret key used to verify the token's signature, as a string or raw bytes.
+// * `token` - The token string to be verified and decoded.
+// * `validation` - The checks to run on the token.
+//
+// # Returns
+//
+// * `Ok(T)` - The deserialized payload if the token is valid.
+// * `Err(TokenError)` - Any error that occurs during the verification process.
+#[cfg(feature = "std")]
+pub fn verify_token_with<T>(
+    secret: impl AsRef<[u8]>,
+    token: &str,
+    validation: &Validation,
+) -> Result<T, TokenError>
+where
+    T: for<'de> Deserialize<'de> + Expirable,
+{
+    verify(secret, token, validation)
+}
+
+// A verified token along with how it was accepted, returned by `verify_detailed`.
+#[cfg(feature = "std")]
+#[derive(Clone)]
+#[cfg_attr(not(feature = "redact-claims"), derive(Debug))]
+#[non_exhaustive]
+pub struct VerifiedToken<T> {
+    // The deserialized payload.
+    pub payload: T,
+    // The algorithm the signature was checked with.
+    pub alg: Algorithm,
+    // The id of the key the token was signed with, if its header names one.
+    pub kid: Option<String>,
+    // How long the token is still valid for.
+    pub remaining: Duration,
+    // The checks the token passed, in the order they ran.
+    pub checks: Vec<Check>,
+}
+
+// Leaves the payload out, since it may hold personal data.
+#[cfg(all(feature = "std", feature = "redact-claims"))]
+impl<T> fmt::Debug for VerifiedToken<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VerifiedToken")
+            .field("alg", &self.alg)
+            .field("kid", &self.kid)
+            .field("remaining", &self.remaining)
+            .field("checks", &self.checks)
+            .finish_non_exhaustive()
+    }
+}
+
+// Verifies a token like `verify` and reports how it was accepted.
+//
+// Besides the payload, the result records the algorithm and key id the
+// token was verified with, its remaining lifetime and exactly which checks
+// ran, for audit logs that must show how each token was accepted.
+//
+// # Arguments
+//
+// * `secret` - The secret key used to verify the token's signature, as a string or raw bytes.
+// * `token` - The token string to be verified and decoded.
+// * `validation` - The checks to run on the token.
+//
+// # Returns
+//
+// * `Ok(VerifiedToken<T>)` - The payload and how it was verified if the token is valid.
+// * `Err(TokenError)` - Any error that occurs during the verification process.
+#[cfg(feature = "std")]
+pub fn verify_detailed<T>(
+    secret: impl AsRef<[u8]>,
+    token: &str,
+    validation: &Validation,
+) -> Result<VerifiedToken<T>, TokenError>
+where
+    T: for<'de> Deserialize<'de> + Expirable,
+{
+    let (header, payload_bytes) = verified_payload(&[secret], token, validation)?;
+    let (payload, failures) = deserialize_required(header.fmt, &payload_bytes, validation)?;
+    validate_claims(&payload, validation, now_millis(), failures)?;
+
+    let checks = checks_run(&payload, validation);
+    Ok(VerifiedToken {
+        remaining: time_until_expiry(&payload),
+        alg: header.alg,
+        kid: header.kid,
+        payload,
+        checks,
+    })
+}
+
+// Verifies a token against several secrets and returns the decoded payload if valid.
+//
+// The token is accepted if its signature matches any of the secrets, which
+// lets tokens signed with an old key be accepted for a grace period while
+// keys are rotated. Every secret is always tried, so the time taken does
+// not reveal which one matched. Expiration is enforced as in `verify_token`.
+//
+// # Arguments
+//
+// * `secrets` - The secret keys the token may have been signed with.
+// * `token` - The token string to be verified and decoded.
+//
+// # Returns
+//
+// * `Ok(T)` - The deserialized payload if the token is valid and not expired.
+// * `Err(TokenError)` - Any error that occurs during the verification process.
+#[cfg(feature = "std")]
+pub fn verify_token_multi<T, S>(secrets: &[S], token: &str) -> Result<T, TokenError>
+where
+    T: for<'de> Deserialize<'de> + Expirable,
+    S: AsRef<[u8]>,
+{
+    verify_token_inner(secrets, token, &Validation::default(), now_millis())
+}
+
+// Verifies many tokens signed with the same secret.
+//
+// The HMAC is keyed once for the whole batch and its state cloned for each
+// token, which saves re-keying when verifying thousands of tokens. Each
+// token is verified independently as in `verify_token`, so one bad token
+// doesn't affect the results for the others.
+//
+// # Arguments
+//
+// * `secret` - The secret key used to verify the tokens' signatures, as a string or raw bytes.
+// * `tokens` - The token strings to be verified and decoded.
+//
+// # Returns
+//
+// * `Vec<Result<T, TokenError>>` - The result for each token, in order.
+#[cfg(feature = "std")]
+pub fn verify_batch<T>(secret: impl AsRef<[u8]>, tokens: &[&str]) -> Vec<Result<T, TokenError>>
+where
+    T: for<'de> Deserialize<'de> + Expirable,
+{
+    let mac = match KeyedMac::new(secret.as_ref()) {
+        Ok(mac) => mac,
+        Err(err) => return tokens.iter().map(|_| Err(err.kind().clone().into())).collect(),
+    };
+    let validation = Validation::default();
+    let now_millis = now_millis();
+
+    tokens
+        .iter()
+        .map(|token| verify_token_inner(&mac, token, &validation, now_millis))
+        .collect()
+}
+
+// Verifies a token and then checks whether it has been revoked.
+//
+// After the signature and expiration checks done by `verify_token` pass,
+// `is_revoked` is called with the token's `jti` and awaited, so the
+// denylist can live in Redis or a database without blocking. Tokens
+// without a `jti` can't appear on a denylist and are not looked up.
+//
+// # Arguments
+//
+// * `secret` - The secret key used to verify the token's signature, as a string or raw bytes.
+// * `token` - The token string to be verified and decoded.
+// * `is_revoked` - Resolves to `true` if the token id has been revoked.
+//
+// # Returns
+//
+// * `Ok(T)` - The deserialized payload if the token is valid and not revoked.
+// * `Err(TokenError)` - Any error that occurs during the verification process.
+#[cfg(feature = "std")]
+pub async fn verify_token_async<T, F, Fut>(
+    secret: impl AsRef<[u8]>,
+    token: &str,
+    is_revoked: F,
+) -> Result<T, TokenError>
+where
+    T: for<'de> Deserialize<'de> + Expirable,
+    F: FnOnce(String) -> Fut,
+    Fut: Future<Output = bool>,
+{
+    let payload: T = verify_token(secret, token)?;
+
+    if let Some(jti) = payload.jti() {
+        if is_revoked(jti.to_string()).await {
+            return Err(TokenErrorKind::Revoked.into());
+        }
+    }
+
+    Ok(payload)
+}
+
+// Verifies a single-use token, rejecting it if its id has already been used.
+//
+// After the checks done by `verify_token` pass, the token's `jti` is
+// recorded in `seen`. A token whose id is already there is rejected even
+// though its signature and expiration are valid, which stops one-time
+// links such as password resets from being replayed. Tokens without a
+// `jti` can't be tracked and are rejected.
+//
+// # Arguments
+//
+// * `secret` - The secret key used to verify the token's signature, as a string or raw bytes.
+// * `token` - The token string to be verified and decoded.
+// * `seen` - The ids of the tokens that have already been used.
+//
+// # Returns
+//
+// * `Ok(T)` - The deserialized payload if the token is valid and has not been used.
+// * `Err(TokenError)` - Any error that occurs during the verification process.
+#[cfg(feature = "std")]
+pub fn verify_token_once<T, N>(
+    secret: impl AsRef<[u8]>,
+    token: &str,
+    seen: &mut N,
+) -> Result<T, TokenError>
+where
+    T: for<'de> Deserialize<'de> + Expirable,
+    N: NonceStore + ?Sized,
+{
+    let payload: T = verify_token(secret, token)?;
+
+    let jti = payload.jti().ok_or(TokenErrorKind::MissingTokenId)?;
+    if !seen.check_and_insert(jti) {
+        return Err(TokenErrorKind::AlreadyUsed.into());
+    }
+
+    Ok(payload)
+}
+
+// Verifies a token that may be used a limited number of times.
+//
+// This generalizes `verify_token_once` to tokens valid for N uses, such as
+// a download link that works three times. After the checks done by
+// `verify_token` pass, `consume` is called with the token's `jti`. It
+// should atomically take one use from the counter kept for that id and
+// return how many are left, or `None` if none were left to take, which
+// rejects the token. The crate never stores the counters itself. Tokens
+// without a `jti` can't be counted and are rejected.
+//
+// # Arguments
+//
+// * `secret` - The secret key used to verify the token's signature, as a string or raw bytes.
+// * `token` - The token string to be verified and decoded.
+// * `consume` - Takes one use of the token id and returns the uses left, if any were.
+//
+// # Returns
+//
+// * `Ok((T, u32))` - The deserialized payload and the uses left after this one.
+// * `Err(TokenError)` - `Exhausted` if the token has no uses left, or any other error that
+//   occurs during the verification process.
+#[cfg(feature = "std")]
+pub fn verify_token_counted<T, F>(
+    secret: impl AsRef<[u8]>,
+    token: &str,
+    mut consume: F,
+) -> Result<(T, u32), TokenError>
+where
+    T: for<'de> Deserialize<'de> + Expirable,
+    F: FnMut(&str) -> Option<u32>,
+{
+    let payload: T = verify_token(secret, token)?;
+
+    let jti = payload.jti().ok_or(TokenErrorKind::MissingTokenId)?;
+    let remaining = consume(jti).ok_or(TokenErrorKind::Exhausted)?;
+
+    Ok((payload, remaining))
+}
+
+// Returns how long the payload is still valid for.
+//
+// This is the time between now and the payload's `exp`, or zero if the
+// payload has already expired.
+//
+// # Arguments
+//
+// * `payload` - The payload to check.
+//
+// # Returns
+//
+// * `Duration` - The remaining lifetime of the payload.
+#[cfg(feature = "std")]
+pub fn time_until_expiry<T>(payload: &T) -> Duration
+where
+    T: Expirable,
+{
+    let now = now_millis();
+    let remaining = match payload.exp_millis() {
+        Some(exp_millis) => exp_millis.saturating_sub(now),
+        None => payload.exp().saturating_mul(1000).saturating_sub(now),
+    };
+    Duration::from_millis(u64::try_from(remaining).unwrap_or(0))
+}
+
+// Verifies a token and returns the decoded payload along with its remaining lifetime.
+//
+// This behaves like `verify_token`, and also returns how long the token is
+// still valid for so callers can decide whether to refresh it early.
+//
+// # Arguments
+//
+// * `secret` - The secret key used to verify the token's signature, as a string or raw bytes.
+// * `token` - The token string to be verified and decoded.
+//
+// # Returns
+//
+// * `Ok((T, Duration))` - The deserialized payload and its remaining lifetime.
+// * `Err(TokenError)` - Any error that occurs during the verification process.
+#[cfg(feature = "std")]
+pub fn verify_token_with_remaining<T>(
+    secret: impl AsRef<[u8]>,
+    token: &str,
+) -> Result<(T, Duration), TokenError>
+where
+    T: for<'de> Deserialize<'de> + Expirable,
+{
+    let payload: T = verify_token(secret, token)?;
+    let remaining = time_until_expiry(&payload);
+    Ok((payload, remaining))
+}
+
+// Verifies a token, returning the payload in the error if it has expired.
+//
+// This verifies the token exactly as `verify_token` does. If the only
+// problem is that the token has expired, the decoded payload is returned in
+// `VerifyError::Expired`, so callers can log whose token expired or use its
+// subject to drive a refresh.
+//
+// # Arguments
+//
+// * `secret` - The secret key used to verify the token's signature, as a string or raw bytes.
+// * `token` - The token string to be verified and decoded.
+//
+// # Returns
+//
+// * `Ok(T)` - The deserialized payload if the token is valid and not expired.
+// * `Err(VerifyError::Expired(T))` - The payload of an authentic but expired token.
+// * `Err(VerifyError::Invalid(TokenError))` - Any other error during verification.
+#[cfg(feature = "std")]
+pub fn verify_token_or_expired<T>(
+    secret: impl AsRef<[u8]>,
+    token: &str,
+) -> Result<T, VerifyError<T>>
+where
+    T: for<'de> Deserialize<'de> + Expirable,
+{
+    let (payload, failures) = decode_verified(&[secret], token, &Validation::default())?;
+    match validate_claims(&payload, &Validation::default(), now_millis(), failures) {
+        Ok(()) => Ok(payload),
+        Err(err) if *err.kind() == TokenErrorKind::Expired => Err(VerifyError::Expired(payload)),
+        Err(err) => Err(err.into()),
+    }
+}
+
+// Verifies a token and checks its claims as of the given time.
+//
+// This is `verify` with the current time supplied by the caller,
+// for targets built without the `std` feature that have no system clock.
+//
+// # Arguments
+//
+// * `secret` - The secret key used to verify the token's signature, as a string or raw bytes.
+// * `token` - The token string to be verified and decoded.
+// * `validation` - The claims to check and the leeway to allow.
+// * `now` - The current time (Unix timestamp).
+//
+// # Returns
+//
+// * `Ok(T)` - The deserialized payload if the token is valid.
+// * `Err(TokenError)` - Any error that occurs during the verification process.
+pub fn verify_token_at<T>(
+    secret: impl AsRef<[u8]>,
+    token: &str,
+    validation: &Validation,
+    now: i64,
+) -> Result<T, TokenError>
+where
+    T: for<'de> Deserialize<'de> + Expirable,
+{
+    verify_token_inner(&[secret], token, validation, now.saturating_mul(1000))
+}
+
+// Verifies a token and checks it against the validation using the given clock.
+//
+// This is `verify` with the current time read from `clock` instead of the
+// system clock, so expiry logic can be tested deterministically without
+// sleeping.
+//
+// # Arguments
+//
+// * `secret` - The secret key used to verify the token's signature, as a string or raw bytes.
+// * `token` - The token string to be verified and decoded.
+// * `validation` - The checks to run on the token.
+// * `clock` - The source of the current time.
+//
+// # Returns
+//
+// * `Ok(T)` - The deserialized payload if the token is valid.
+// * `Err(TokenError)` - Any error that occurs during the verification process.
+pub fn verify_with_clock<T>(
+    secret: impl AsRef<[u8]>,
+    token: &str,
+    validation: &Validation,
+    clock: &impl Clock,
+) -> Result<T, TokenError>
+where
+    T: for<'de> Deserialize<'de> + Expirable,
+{
+    verify_token_inner(&[secret], token, validation, clock.now_millis())
+}
+
+// Returns the current time from the system clock (Unix timestamp in milliseconds).
+#[cfg(feature = "std")]
+fn now_millis() -> i64 {
+    SystemClock.now_millis()
+}
+
+fn verify_token_inner<T, V>(
+    verifier: &V,
+    token: &str,
+    validation: &Validation,
+    now_millis: i64,
+) -> Result<T, TokenError>
+where
+    T: for<'de> Deserialize<'de> + Expirable,
+    V: SignatureVerifier + ?Sized,
+{
+    let (payload, failures) = decode_verified(verifier, token, validation)?;
+    validate_claims(&payload, validation, now_millis, failures)
+        .inspect_err(|err| log_rejected("claims", None, err))?;
+    Ok(payload)
+}
+
+// Reports a rejected token through the `log` crate, at debug level under
+// the `crabtoken` target, with the step that rejected it and the
+// algorithm and key id of its header once that has been read. Only the
+// error is logged, never the secret or the token, and errors name claims
+// rather than quote their values.
+#[cfg(feature = "log")]
+fn log_rejected(step: &str, header: Option<&Header>, err: &TokenError) {
+    match header {
+        Some(header) => log::debug!(
+            target: "crabtoken",
+            "token rejected at {} (alg {}, kid {:?}): {}",
+            step,
+            header.alg.name(),
+            header.kid.as_deref(),
+            err
+        ),
+        None => log::debug!(target: "crabtoken", "token rejected at {}: {}", step, err),
+    }
+}
+
+// Without the `log` feature, rejected tokens are not reported.
+#[cfg(not(feature = "log"))]
+#[inline(always)]
+fn log_rejected(_step: &str, _header: Option<&Header>, _err: &TokenError) {}
+
+// Verifies a token's signature and returns the decoded payload, ignoring expiration.
+//
+// The signature is checked exactly as in `verify_token`, but the `exp` and
+// `nbf` claims are not. This is useful for refresh flows that accept a
+// recently-expired token as proof of prior authentication.
+//
+// To verify tokens that never expire, use `verify_token_no_exp`, which
+// says so at the call site.
+//
+// # Arguments
+//
+// * `secret` - The secret key used to verify the token's signature, as a string or raw bytes.
+// * `token` - The token string to be verified and decoded.
+//
+// # Returns
+//
+// * `Ok(T)` - The deserialized payload if the signature is valid.
+// * `Err(TokenError)` - Any error that occurs during the verification process.
+pub fn verify_signature_only<T>(secret: impl AsRef<[u8]>, token: &str) -> Result<T, TokenError>
+where
+    T: for<'de> Deserialize<'de>,
+{
+    decode_verified(&[secret], token, &Validation::default()).map(|(payload, _)| payload)
+}
+
+// Verifies a token that never expires and returns the decoded payload.
+//
+// This is for long-lived credentials, such as service API keys that are
+// revoked by hand. `T` need not implement `Expirable`, so the payload
+// doesn't need an `exp` at all, and any `exp` or `nbf` it has is ignored.
+// The signature is checked exactly as in `verify_token`.
+//
+// # Arguments
+//
+// * `secret` - The secret key used to verify the token's signature, as a string or raw bytes.
+// * `token` - The token string to be verified and decoded.
+//
+// # Returns
+//
+// * `Ok(T)` - The deserialized payload if the signature is valid.
+// * `Err(TokenError)` - Any error that occurs during the verification process.
+pub fn verify_token_no_exp<T>(secret: impl AsRef<[u8]>, token: &str) -> Result<T, TokenError>
+where
+    T: for<'de> Deserialize<'de>,
+{
+    verify_signature_only(secret, token)
+}
+
+// Verifies a token created by `create_token_raw` and returns its payload bytes.
+//
+// Only the signature is checked, since a raw payload has no claims to
+// check. Tokens whose payload is not raw are rejected.
+//
+// # Arguments
+//
+// * `secret` - The secret key used to verify the token's signature, as a string or raw bytes.
+// * `token` - The token string to be verified.
+//
+// # Returns
+//
+// * `Ok(Vec<u8>)` - The payload bytes if the signature is valid.
+// * `Err(TokenError)` - Any error that occurs during the verification process.
+pub fn verify_token_raw(secret: impl AsRef<[u8]>, token: &str) -> Result<Vec<u8>, TokenError> {
+    let (header, payload_bytes) = verified_payload(&[secret], token, &Validation::default())?;
+    if header.fmt != Format::Raw {
+        return Err(TokenErrorKind::InvalidFormat.into());
+    }
+    Ok(payload_bytes)
+}
+
+// Decodes a token after checking its signature and the header checks of
+// the validation (algorithm, legacy format and type), and the claims
+// checked on the decoded payload. The failures collected from those, with
+// `Validation::collect_errors`, are returned for `validate_claims`.
+fn decode_verified<T, V>(
+    verifier: &V,
+    token: &str,
+    validation: &Validation,
+) -> Result<(T, Vec<TokenErrorKind>), TokenError>
+where
+    T: for<'de> Deserialize<'de>,
+    V: SignatureVerifier + ?Sized,
+{
+    let (header, payload_bytes) = verified_payload(verifier, token, validation)?;
+    deserialize_required(header.fmt, &payload_bytes, validation)
+        .inspect_err(|err| log_rejected("payload", Some(&header), err))
+}
+
+// Deserializes a verified payload, checking first that it has the claims
+// and scopes the validation requires, and conforms to its schema.
+//
+// With `Validation::collect_errors` the failures of those checks are
+// returned alongside the payload instead, to be reported together with
+// those of `validate_claims`.
+fn deserialize_required<T>(
+    format: Format,
+    payload_bytes: &[u8],
+    validation: &Validation,
+) -> Result<(T, Vec<TokenErrorKind>), TokenError>
+where
+    T: for<'de> Deserialize<'de>,
+{
+    let mut failures = Vec::new();
+    if !validation.required_claims.is_empty()
+        || !validation.required_scopes.is_empty()
+        || validation.schema.is_some()
+    {
+        let claims: serde_json::Value =
+            deserialize_payload(format, payload_bytes, validation.max_depth)?;
+        failures = check_payload_claims(&claims, validation)?;
+    }
+    match deserialize_payload(format, payload_bytes, validation.max_depth) {
+        Ok(payload) => Ok((payload, failures)),
+        // A payload missing its required claims may not fit `T` either;
+        // the collected failures say why.
+        Err(_) if !failures.is_empty() => Err(collected_error(failures)),
+        Err(err) => Err(err),
+    }
+}
+
+// Returns the header and payload bytes of a token after checking its
+// signature and the header checks of the validation. Legacy tokens get
+// the version 0 header they would have had.
+fn verified_payload<V>(
+    verifier: &V,
+    token: &str,
+    validation: &Validation,
+) -> Result<(Header, Vec<u8>), TokenError>
+where
+    V: SignatureVerifier + ?Sized,
+{
+    let parse_failed = |err: &TokenError| log_rejected("parse", None, err);
+    check_token_len(token, validation.max_token_len).inspect_err(parse_failed)?;
+
+    if validation.allow_legacy && is_legacy_token(token) {
+        let (payload_bytes, signature) =
+            parse_legacy_token(token, validation.encoding).inspect_err(parse_failed)?;
+        let algorithms = &validation.algorithms;
+        check_signature(verifier, Algorithm::Hs256, &payload_bytes, &signature, algorithms)
+            .inspect_err(|err| log_rejected("signature", Some(&legacy_header()), err))?;
+        // Legacy tokens have no header, so they never have a type.
+        if validation.expected_typ.is_some() {
+            let err = TokenErrorKind::InvalidType.into();
+            log_rejected("type", Some(&legacy_header()), &err);
+            return Err(err);
+        }
+        Ok((legacy_header(), payload_bytes))
+    } else {
+        let segments = split_token(token).inspect_err(parse_failed)?;
+        verified_segments(verifier, segments, signing_input(token).as_bytes(), validation)
+    }
+}
+
+// Returns the header and payload bytes of a token split into its header,
+// payload and signature segments, after checking its signature over
+// `signing_input` and the header checks of the validation.
+fn verified_segments<V>(
+    verifier: &V,
+    (header, payload, signature): (&str, &str, &str),
+    signing_input: &[u8],
+    validation: &Validation,
+) -> Result<(Header, Vec<u8>), TokenError>
+where
+    V: SignatureVerifier + ?Sized,
+{
+    let encoding = validation.encoding;
+    let parse_failed = |err: &TokenError| log_rejected("parse", None, err);
+    // The header and signature are short, so they are decoded into
+    // buffers on the stack rather than allocated for every token.
+    let mut header_buffer = [0; HEADER_BUFFER_LEN];
+    let header_bytes = decode_segment_buffered("header", header, encoding, &mut header_buffer)
+        .inspect_err(parse_failed)?;
+    let payload_bytes = decode_segment("payload", payload, encoding).inspect_err(parse_failed)?;
+    let mut signature_buffer = [0; SIGNATURE_BUFFER_LEN];
+    let signature =
+        decode_segment_buffered("signature", signature, encoding, &mut signature_buffer)
+            .inspect_err(parse_failed)?;
+    let header = read_header(&header_bytes).inspect_err(parse_failed)?;
+
+    check_signature(verifier, header.alg, signing_input, &signature, &validation.algorithms)
+        .inspect_err(|err| log_rejected("signature", Some(&header), err))?;
+    if let Some(expected_typ) = &validation.expected_typ {
+        if header.typ.as_deref() != Some(expected_typ.as_str()) {
+            let err = TokenErrorKind::InvalidType.into();
+            log_rejected("type", Some(&header), &err);
+            return Err(err);
+        }
+    }
+    Ok((header, payload_bytes))
+}
+
+// Returns the header of a token that is about to be verified, without
+// checking its signature. Legacy tokens get the version 0 header they
+// would have had if the validation accepts them.
+fn unverified_header(token: &str, validation: &Validation) -> Result<Header, TokenError> {
+    check_token_len(token, validation.max_token_len)?;
+    if validation.allow_legacy && is_legacy_token(token) {
+        return Ok(legacy_header());
+    }
+    let (header, _, _) = split_token(token)?;
+    read_header(&decode_segment("header", header, validation.encoding)?)
+}
+
+// The header implied by a legacy token, which had none.
+fn legacy_header() -> Header {
+    Header {
+        ver: 0,
+        alg: Algorithm::Hs256,
+        fmt: Format::MessagePack,
+        kid: None,
+        typ: None,
+    }
+}
+
+// Rejects tokens longer than `max_len` before anything is decoded, so an
+// oversized token can't force large allocations.
+fn check_token_len(token: &str, max_len: usize) -> Result<(), TokenError> {
+    if token.len() > max_len {
+        return Err(TokenErrorKind::TooLarge.into());
+    }
+    Ok(())
+}
+
+// Checks a signature made with `alg`, provided `alg` is one of the
+// accepted algorithms.
+fn check_signature<V>(
+    verifier: &V,
+    alg: Algorithm,
+    signing_input: &[u8],
+    signature: &[u8],
+    algorithms: &[Algorithm],
+) -> Result<(), TokenError>
+where
+    V: SignatureVerifier + ?Sized,
+{
+    // The allowlist is always applied: an empty list accepts nothing.
+    if !algorithms.contains(&alg) {
+        return Err(TokenErrorKind::AlgorithmMismatch.into());
+    }
+    // A signature of the wrong length can't match whatever the secret, so
+    // the token was truncated or corrupted rather than forged.
+    if signature.len() != alg.signature_len() {
+        return Err(TokenErrorKind::BadSignatureLength.into());
+    }
+
+    verifier.verify(alg, signing_input, signature)
+}
+
+// Verifies a token using the secret registered for the key id in its header.
+//
+// Tokens created with `TokenOptions::kid` carry the id of the key that
+// signed them. `lookup` is called with that id and returns the matching
+// secret, so a verifier holding many keys only checks the right one.
+// Tokens without a key id, or with one `lookup` doesn't know, are rejected.
+//
+// # Arguments
+//
+// * `lookup` - Returns the secret for a key id, or `None` if it is unknown.
+// * `token` - The token string to be verified and decoded.
+//
+// # Returns
+//
+// * `Ok(T)` - The deserialized payload if the token is valid and not expired.
+// * `Err(TokenError)` - Any error that occurs during the verification process.
+#[cfg(feature = "std")]
+pub fn verify_token_by_kid<T, F>(lookup: F, token: &str) -> Result<T, TokenError>
+where
+    T: for<'de> Deserialize<'de> + Expirable,
+    F: FnMut(&str) -> Option<SecretKey>,
+{
+    verify_by_kid_with(lookup, token, &Validation::default())
+}
+
+// Verifies a token like `verify_token_by_kid`, checking it like `verify`
+// with `validation`.
+//
+// The algorithm allowlist, length and depth limits of `validation` apply
+// before `lookup` is called, and its claim checks after the signature.
+//
+// # Arguments
+//
+// * `lookup` - Returns the secret for a key id, or `None` if it is unknown.
+// * `token` - The token string to be verified and decoded.
+// * `validation` - The checks to run on the token.
+//
+// # Returns
+//
+// * `Ok(T)` - The deserialized payload if the token is valid.
+// * `Err(TokenError)` - Any error that occurs during the verification process.
+#[cfg(feature = "std")]
+pub fn verify_by_kid_with<T, F>(
+    mut lookup: F,
+    token: &str,
+    validation: &Validation,
+) -> Result<T, TokenError>
+where
+    T: for<'de> Deserialize<'de> + Expirable,
+    F: FnMut(&str) -> Option<SecretKey>,
+{
+    let header = unverified_header(token, validation)?;
+    let kid = header.kid.as_deref().ok_or(TokenErrorKind::MissingKeyId)?;
+    let secret = lookup(kid).ok_or(TokenErrorKind::UnknownKeyId)?;
+    verify_token_inner(&[secret], token, validation, now_millis())
+}
+
+// The decoded segments of a token.
+#[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(not(feature = "redact-claims"), derive(Debug))]
+pub struct TokenParts {
+    // The raw bytes of the header segment.
+    pub header_bytes: Vec<u8>,
+    // The raw bytes of the payload segment, as serialized at creation.
+    pub payload_bytes: Vec<u8>,
+    // The raw signature bytes.
+    pub signature: Vec<u8>,
+}
+
+// Prints the length of the payload rather than its bytes.
+#[cfg(feature = "redact-claims")]
+impl fmt::Debug for TokenParts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TokenParts")
+            .field("header_bytes", &self.header_bytes)
+            .field("payload_bytes", &format_args!("<{} bytes>", self.payload_bytes.len()))
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+// Splits a token into its segments and base64url-decodes each of them.
+//
+// This does not deserialize the payload or check the signature, so it can
+// be used by tooling to inspect any token.
+//
+// # Arguments
+//
+// * `token` - The token string to be parsed.
+//
+// # Returns
+//
+// * `Ok(TokenParts)` - The decoded header, payload and signature bytes.
+// * `Err(TokenError)` - The token is malformed.
+pub fn parse_token(token: &str) -> Result<TokenParts, TokenError> {
+    parse_token_with_encoding(token, Encoding::default())
+}
+
+// Splits a token into its segments and decodes each of them with the given encoding.
+//
+// This is `parse_token` for tokens created with a non-default
+// `TokenOptions::encoding`.
+//
+// # Arguments
+//
+// * `token` - The token string to be parsed.
+// * `encoding` - The base64 variant the token was created with.
+//
+// # Returns
+//
+// * `Ok(TokenParts)` - The decoded header, payload and signature bytes.
+// * `Err(TokenError)` - The token is malformed.
+pub fn parse_token_with_encoding(
+    token: &str,
+    encoding: Encoding,
+) -> Result<TokenParts, TokenError> {
+    let (header, payload, signature) = split_token(token)?;
+
+    Ok(TokenParts {
+        header_bytes: decode_segment("header", header, encoding)?,
+        payload_bytes: decode_segment("payload", payload, encoding)?,
+        signature: decode_segment("signature", signature, encoding)?,
+    })
+}
+
+// Decodes only the header of a token, without checking its signature.
+//
+// Middleware can use this to route a token by its key id or type, or to
+// pick the key to verify it with, before doing any verification. Only the
+// first segment is decoded. Nothing in the header can be trusted until
+// the token has been verified. Claims such as `iss` are in the payload,
+// so reading them takes `decode_token` or `introspect`.
+//
+// # Arguments
+//
+// * `token` - The token string whose header is decoded.
+//
+// # Returns
+//
+// * `Ok(Header)` - The decoded header.
+// * `Err(TokenError)` - The token or its header is malformed.
+pub fn decode_header(token: &str) -> Result<Header, TokenError> {
+    check_token_len(token, DEFAULT_MAX_TOKEN_LEN)?;
+    let (header, _, _) = split_token(token)?;
+    read_header(&decode_segment("header", header, Encoding::default())?)
+}
+
+// Reads a token's expiration without deserializing the rest of its payload.
+//
+// Only the `exp` claim is read, so a cache can bucket tokens by lifetime
+// without building a large payload type for each. This does NOT verify
+// the token: the signature is not checked, and the expiration says nothing
+// until the token has been verified. Payloads must be maps keyed by name,
+// as JSON and MessagePack payloads are by default; any other payload,
+// such as one created with `named_fields` off, fails with
+// `PayloadTypeMismatch` rather than being read by position.
+//
+// # Arguments
+//
+// * `token` - The token string whose expiration is read.
+//
+// # Returns
+//
+// * `Ok(i64)` - The `exp` claim (Unix timestamp).
+// * `Err(TokenError)` - The token is malformed, its payload is not a map, or it has no
+//   `exp` claim.
+pub fn peek_exp(token: &str) -> Result<i64, TokenError> {
+    #[derive(Deserialize)]
+    struct Exp {
+        exp: Option<i64>,
+    }
+
+    check_token_len(token, DEFAULT_MAX_TOKEN_LEN)?;
+    let (header, payload, _) = split_token(token)?;
+    let header = read_header(&decode_segment("header", header, Encoding::default())?)?;
+    let payload_bytes = decode_segment("payload", payload, Encoding::default())?;
+
+    // A struct deserializes from an array by position, which would read
+    // whatever field comes first as `exp`.
+    let found = match header.fmt {
+        Format::MessagePack => shape::msgpack_non_map(&payload_bytes),
+        Format::Json => shape::json_non_map(&payload_bytes),
+        Format::Raw => None,
+    };
+    if let Some(found) = found {
+        let expected = "a map with an exp claim".to_string();
+        return Err(TokenErrorKind::PayloadTypeMismatch { expected, found }.into());
+    }
+
+    let payload: Exp = deserialize_payload(header.fmt, &payload_bytes, DEFAULT_MAX_DEPTH)?;
+    payload.exp.ok_or_else(|| TokenErrorKind::MissingClaim("exp".to_string()).into())
+}
+
+// A readable view of a token, returned by `introspect`.
+#[derive(Clone, PartialEq)]
+#[cfg_attr(not(feature = "redact-claims"), derive(Debug))]
+pub struct TokenInfo {
+    // The algorithm the token says it was signed with.
+    pub alg: Algorithm,
+    // The encoding of the payload.
+    pub format: Format,
+    // The id of the signing key, if any.
+    pub kid: Option<String>,
+    // The type of the token, if any.
+    pub typ: Option<String>,
+    // The payload as a dynamic value.
+    pub payload: serde_json::Value,
+    // The raw signature bytes.
+    pub signature: Vec<u8>,
+}
+
+// Prints the names of the claims rather than their values.
+#[cfg(feature = "redact-claims")]
+impl fmt::Debug for TokenInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let claims: Vec<&str> = match &self.payload {
+            serde_json::Value::Object(claims) => claims.keys().map(String::as_str).collect(),
+            _ => Vec::new(),
+        };
+        f.debug_struct("TokenInfo")
+            .field("alg", &self.alg)
+            .field("format", &self.format)
+            .field("kid", &self.kid)
+            .field("typ", &self.typ)
+            .field("claims", &claims)
+            .field("signature", &self.signature)
+            .finish()
+    }
+}
+
+// Decodes any token into a readable view, for logging and debugging.
+//
+// The payload is decoded as a dynamic value, so a token can be inspected
+// without knowing the type it was created from. Structs appear as maps
+// keyed by field name, except those encoded as MessagePack with
+// `TokenOptions::named_fields` off, which appear as arrays of their field
+// values, and raw payloads appear as arrays of bytes. Like
+// `decode_token`, this does not check the signature or any claims, so
+// nothing in the result can be trusted.
+//
+// # Arguments
+//
+// * `token` - The token string to be inspected.
+//
+// # Returns
+//
+// * `Ok(TokenInfo)` - The header fields, payload and signature of the token.
+// * `Err(TokenError)` - The token is malformed.
+pub fn introspect(token: &str) -> Result<TokenInfo, TokenError> {
+    check_token_len(token, DEFAULT_MAX_TOKEN_LEN)?;
+    let parts = parse_token(token)?;
+    let header = read_header(&parts.header_bytes)?;
+
+    let payload = match header.fmt {
+        Format::Raw => serde_json::Value::from(parts.payload_bytes),
+        format => deserialize_payload(format, &parts.payload_bytes, DEFAULT_MAX_DEPTH)?,
+    };
+
+    Ok(TokenInfo {
+        alg: header.alg,
+        format: header.fmt,
+        kid: header.kid,
+        typ: header.typ,
+        payload,
+        signature: parts.signature,
+    })
+}
+
+// Checks whether a token has the structure of a token, without decoding it.
+//
+// The token must have three non-empty segments made up only of base64url
+// characters, each of a length that base64 can produce, so tokens using
+// the standard alphabet are not considered well-formed. Nothing is
+// allocated, decoded or signed, so a gateway can cheaply drop junk input
+// before spending any effort on verifying it. Tokens in the original
+// two-segment format are not considered well-formed.
+//
+// # Arguments
+//
+// * `token` - The token string to be checked.
+//
+// # Returns
+//
+// * `bool` - Whether the token is well-formed. A well-formed token may
+//   still fail to decode or verify.
+pub fn is_well_formed(token: &str) -> bool {
+    match split_token(token) {
+        Ok((header, payload, signature)) => {
+            is_base64url(header) && is_base64url(payload) && is_base64url(signature)
+        }
+        Err(_) => false,
+    }
+}
+
+// Returns whether the segment is base64url with valid length, optionally
+// followed by `=` padding.
+fn is_base64url(segment: &str) -> bool {
+    let unpadded = segment.trim_end_matches('=');
+    let padding = segment.len() - unpadded.len();
+    let valid_padding = match padding {
+        0 => true,
+        1 | 2 => segment.len().is_multiple_of(4),
+        _ => false,
+    };
+
+    valid_padding
+        && unpadded.len() % 4 != 1
+        && unpadded.bytes().all(|byte| byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_')
+}
+
+// Splits a token into its header, payload and signature segments without allocating.
+//
+// Any `.` left in the signature segment means the token has too many
+// segments and is rejected. Empty segments, as in a truncated token, are
+// rejected before any decoding is attempted.
+fn split_token(token: &str) -> Result<(&str, &str, &str), TokenError> {
+    let (header, rest) = token.split_once('.').ok_or(TokenErrorKind::InvalidFormat)?;
+    let (payload, signature) = rest.split_once('.').ok_or(TokenErrorKind::InvalidFormat)?;
+    if signature.contains('.') || header.is_empty() || payload.is_empty() || signature.is_empty() {
+        return Err(TokenErrorKind::InvalidFormat.into());
+    }
+    Ok((header, payload, signature))
+}
+
+// Returns the part of a well-formed token covered by its signature.
+fn signing_input(token: &str) -> &str {
+    token.rsplit_once('.').map_or(token, |(signing_input, _)| signing_input)
+}
+
+// Returns whether the token uses the original two-segment format.
+fn is_legacy_token(token: &str) -> bool {
+    token
+        .split_once('.')
+        .is_some_and(|(_, signature)| !signature.contains('.'))
+}
+
+// Splits a two-segment legacy token into its payload and signature bytes.
+fn parse_legacy_token(
+    token: &str,
+    encoding: Encoding,
+) -> Result<(Vec<u8>, Vec<u8>), TokenError> {
+    let (payload, signature) = token.split_once('.').ok_or(TokenErrorKind::InvalidFormat)?;
+    if payload.is_empty() || signature.is_empty() {
+        return Err(TokenErrorKind::InvalidFormat.into());
+    }
+    Ok((
+        decode_segment("payload", payload, encoding)?,
+        decode_segment("signature", signature, encoding)?,
+    ))
+}
+
+// Base64url decoding that accepts segments with or without `=` padding.
+//
+// Tokens are created unpadded by default, but some JWT tooling pads each
+// segment, and those tokens are accepted too.
+const URL_SAFE_LENIENT: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::URL_SAFE,
+    GeneralPurposeConfig::new()
+        .with_encode_padding(false)
+        .with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+// Standard base64 decoding that accepts segments with or without `=` padding.
+const STANDARD_LENIENT: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::STANDARD,
+    GeneralPurposeConfig::new()
+        .with_encode_padding(false)
+        .with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+// Base64-decodes a token segment.
+//
+// `name` identifies the segment in the error if it is malformed, so it is
+// clear whether the header, payload or signature was at fault.
+fn decode_segment(
+    name: &'static str,
+    segment: &str,
+    encoding: Encoding,
+) -> Result<Vec<u8>, TokenError> {
+    let mut bytes = Vec::new();
+    decode_segment_into(name, segment, encoding, &mut bytes)?;
+    Ok(bytes)
+}
+
+// The size of the stack buffers the header and signature of a token are
+// decoded into when it is verified. Every signature fits, and so does any
+// header whose key id and type are not unusually long.
+const HEADER_BUFFER_LEN: usize = 128;
+const SIGNATURE_BUFFER_LEN: usize = 64;
+
+// Base64-decodes a token segment into `buffer`, or into a new `Vec` if it
+// doesn't fit, so short segments are decoded without allocating.
+fn decode_segment_buffered<'a>(
+    name: &'static str,
+    segment: &str,
+    encoding: Encoding,
+    buffer: &'a mut [u8],
+) -> Result<Cow<'a, [u8]>, TokenError> {
+    match encoding.decoder().decode_slice(segment, buffer) {
+        Ok(len) => Ok(Cow::Borrowed(&buffer[..len])),
+        Err(DecodeSliceError::OutputSliceTooSmall) => {
+            decode_segment(name, segment, encoding).map(Cow::Owned)
+        }
+        Err(DecodeSliceError::DecodeError(err)) => Err(segment_error(name, err)),
+    }
+}
+
+// Base64-decodes a token segment, appending the bytes to `buffer`.
+fn decode_segment_into(
+    name: &'static str,
+    segment: &str,
+    encoding: Encoding,
+    buffer: &mut Vec<u8>,
+) -> Result<(), TokenError> {
+    encoding
+        .decoder()
+        .decode_vec(segment, buffer)
+        .map_err(|err| segment_error(name, err))
+}
+
+// The error for a segment that is not valid base64.
+//
+// Characters outside the expected alphabet, such as `+` and `/` in a
+// url-safe token or whitespace, and misplaced `=` padding are reported as
+// `InvalidEncoding` with the segment's name. Standard-alphabet input is never accepted leniently
+// in place of url-safe input or the other way round; set
+// `Validation::encoding` to the variant tokens are encoded with. Padding
+// in the right place is always accepted.
+fn segment_error(name: &'static str, err: DecodeError) -> TokenError {
+    match err {
+        DecodeError::InvalidByte(..) | DecodeError::InvalidPadding => {
+            caused_by(TokenErrorKind::InvalidEncoding(name), err)
+        }
+        DecodeError::InvalidLength(_) | DecodeError::InvalidLastSymbol(..) => {
+            caused_by(TokenErrorKind::Decode(format!("Invalid {} segment", name)), err)
+        }
+    }
+}
+
+// Decodes a token and returns the payload if valid.
+//
+// This function decodes the token and deserializes the payload into the type `T`.
+// It does not check for signature or expiration, making it suitable for use cases
+// where only the payload is needed.
+// Tokens longer than `DEFAULT_MAX_TOKEN_LEN` are rejected without being decoded.
+//
+// # Arguments
+//
+// * `token` - The token string to be decoded.
+//
+// # Returns
+//
+// * `Ok(T)` - The deserialized payload if the token is valid.
+// * `Err(TokenError)` - Any error that occurs during the decoding process.
+pub fn decode_token<T>(token: &str) -> Result<T, TokenError>
+where
+    T: for<'de> Deserialize<'de>,
+{
+    decode_token_with_buffer(token, &mut Vec::new())
+}
+
+// Decodes a token created with the given encoding and returns the payload.
+//
+// This is `decode_token` for tokens created with a non-default
+// `TokenOptions::encoding`. Like `decode_token`, it does not check the
+// signature or expiration.
+//
+// # Arguments
+//
+// * `token` - The token string to be decoded.
+// * `encoding` - The base64 variant the token was created with.
+//
+// # Returns
+//
+// * `Ok(T)` - The deserialized payload if the token is valid.
+// * `Err(TokenError)` - Any error that occurs during the decoding process.
+pub fn decode_token_with_encoding<T>(token: &str, encoding: Encoding) -> Result<T, TokenError>
+where
+    T: for<'de> Deserialize<'de>,
+{
+    decode_into(token, encoding, &mut Vec::new())
+}
+
+// Decodes a token like `decode_token`, reusing the provided buffer.
+//
+// The segments are decoded into `buffer` instead of freshly allocated
+// vectors, so a hot path that decodes many tokens can keep one buffer
+// around and avoid allocating for each of them. The buffer is cleared
+// first and holds the decoded segments afterwards.
+//
+// # Arguments
+//
+// * `token` - The token string to be decoded.
+// * `buffer` - The buffer to decode the token segments into.
+//
+// # Returns
+//
+// * `Ok(T)` - The deserialized payload if the token is valid.
+// * `Err(TokenError)` - Any error that occurs during the decoding process.
+pub fn decode_token_with_buffer<T>(token: &str, buffer: &mut Vec<u8>) -> Result<T, TokenError>
+where
+    T: for<'de> Deserialize<'de>,
+{
+    decode_into(token, Encoding::default(), buffer)
+}
+
+// Returns whether two tokens carry the same claims.
+//
+// Both payloads are decoded as in `decode_token` and compared as dynamic
+// values, so the order of the claims, the payload format and the secret
+// the tokens were signed with make no difference. This is useful to
+// deduplicate tokens, or to check that a reissued token kept its claims.
+// Neither token is verified: the signatures and the expiration are
+// ignored, so the result says nothing about whether either is valid.
+//
+// # Arguments
+//
+// * `token_a` - The first token to compare.
+// * `token_b` - The second token to compare.
+//
+// # Returns
+//
+// * `Ok(bool)` - Whether both tokens carry the same claims.
+// * `Err(TokenError)` - Any error that occurs while decoding either token.
+pub fn same_claims(token_a: &str, token_b: &str) -> Result<bool, TokenError> {
+    let claims_a: serde_json::Value = decode_token(token_a)?;
+    let claims_b: serde_json::Value = decode_token(token_b)?;
+    Ok(claims_a == claims_b)
+}
+
+// Returns a short fingerprint of the token, for logs and cache keys.
+//
+// The fingerprint is the first 16 bytes of the SHA-256 hash of the whole
+// token, as 32 lowercase hex digits. The same token always has the same
+// fingerprint, so log lines can be correlated without storing the token,
+// and the token can't be recovered from it. No secret is needed and the
+// payload is not decoded. Any string is accepted, so the fingerprint says
+// nothing about whether the token is valid.
+//
+// # Arguments
+//
+// * `token` - The token to fingerprint.
+//
+// # Returns
+//
+// * `String` - The fingerprint of the token.
+pub fn fingerprint(token: &str) -> String {
+    let hash = Sha256::digest(token.as_bytes());
+    hash[..16].iter().fold(String::with_capacity(32), |mut hex, byte| {
+        // Writing to a `String` can't fail.
+        let _ = write!(hex, "{:02x}", byte);
+        hex
+    })
+}
+
+// Decodes the token's segments into `buffer` and deserializes the payload.
+fn decode_into<T>(token: &str, encoding: Encoding, buffer: &mut Vec<u8>) -> Result<T, TokenError>
+where
+    T: for<'de> Deserialize<'de>,
+{
+    check_token_len(token, DEFAULT_MAX_TOKEN_LEN)?;
+    let (header, payload, signature) = split_token(token)?;
+
+    buffer.clear();
+    decode_segment_into("header", header, encoding, buffer)?;
+    let header_end = buffer.len();
+    decode_segment_into("payload", payload, encoding, buffer)?;
+    let payload_end = buffer.len();
+    decode_segment_into("signature", signature, encoding, buffer)?;
+
+    let header = read_header(&buffer[..header_end])?;
+    let payload_bytes = &buffer[header_end..payload_end];
+    let payload: T = deserialize_payload(header.fmt, payload_bytes, DEFAULT_MAX_DEPTH)?;
+
+    Ok(payload)
+}
+
+// Decodes a token in the original two-segment `payload.signature` format.
+//
+// Tokens created by crate versions before the header segment was added
+// are rejected by `decode_token`. This decodes them so they can be
+// migrated; to verify them, set `Validation::allow_legacy`. Like
+// `decode_token`, it does not check the signature or expiration.
+//
+// # Arguments
+//
+// * `token` - The legacy token string to be decoded.
+//
+// # Returns
+//
+// * `Ok(T)` - The deserialized payload if the token is a valid legacy token.
+// * `Err(TokenError)` - Any error that occurs during the decoding process.
+pub fn decode_legacy_token<T>(token: &str) -> Result<T, TokenError>
+where
+    T: for<'de> Deserialize<'de>,
+{
+    if !is_legacy_token(token) {
+        return Err(TokenErrorKind::InvalidFormat.into());
+    }
+
+    let (payload_bytes, _) = parse_legacy_token(token, Encoding::default())?;
+    msgpack_from_slice(&payload_bytes, DEFAULT_MAX_DEPTH)
+}
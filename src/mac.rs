@@ -0,0 +1,231 @@
+use crate::key::check_secret;
+use crate::{
+    verify_signature, Algorithm, HmacSha256, HmacSha384, HmacSha512, TokenError, TokenErrorKind,
+};
+use alloc::vec::Vec;
+use hmac::digest::KeyInit;
+use hmac::Mac;
+use sha2::{Sha256, Sha384, Sha512};
+use subtle::ConstantTimeEq;
+
+// Something that can check a token signature made with a given algorithm.
+pub(crate) trait SignatureVerifier {
+    fn verify(
+        &self,
+        alg: Algorithm,
+        signing_input: &[u8],
+        signature: &[u8],
+    ) -> Result<(), TokenError>;
+}
+
+// A list of secrets accepts a signature matching any of them. Every secret
+// is always tried, so the time taken does not reveal which one matched.
+impl<S> SignatureVerifier for [S]
+where
+    S: AsRef<[u8]>,
+{
+    fn verify(
+        &self,
+        alg: Algorithm,
+        signing_input: &[u8],
+        signature: &[u8],
+    ) -> Result<(), TokenError> {
+        let mut verified = false;
+        for secret in self {
+            match verify_signature(alg, secret.as_ref(), signing_input, signature) {
+                Ok(()) => verified = true,
+                Err(err) if *err.kind() == TokenErrorKind::InvalidSignature => {}
+                Err(err) => return Err(err),
+            }
+        }
+        if !verified {
+            return Err(TokenErrorKind::InvalidSignature.into());
+        }
+        Ok(())
+    }
+}
+
+impl<S, const N: usize> SignatureVerifier for [S; N]
+where
+    S: AsRef<[u8]>,
+{
+    fn verify(
+        &self,
+        alg: Algorithm,
+        signing_input: &[u8],
+        signature: &[u8],
+    ) -> Result<(), TokenError> {
+        self.as_slice().verify(alg, signing_input, signature)
+    }
+}
+
+// Checks signatures that also cover associated data, see `with_aad`.
+#[cfg(feature = "std")]
+pub(crate) struct WithAad<'a, V: ?Sized> {
+    pub(crate) verifier: &'a V,
+    pub(crate) aad: &'a [u8],
+}
+
+#[cfg(feature = "std")]
+impl<V> SignatureVerifier for WithAad<'_, V>
+where
+    V: SignatureVerifier + ?Sized,
+{
+    fn verify(
+        &self,
+        alg: Algorithm,
+        signing_input: &[u8],
+        signature: &[u8],
+    ) -> Result<(), TokenError> {
+        self.verifier.verify(alg, &with_aad(signing_input, self.aad), signature)
+    }
+}
+
+// Returns the text signed for a token bound to associated data: the
+// signing input, then the data, then its length as a big-endian u64. The
+// length keeps bytes from being moved between the token and the data
+// without changing the signature.
+pub(crate) fn with_aad(signing_input: &[u8], aad: &[u8]) -> Vec<u8> {
+    let mut signed = Vec::with_capacity(signing_input.len() + aad.len() + 8);
+    signed.extend_from_slice(signing_input);
+    signed.extend_from_slice(aad);
+    signed.extend_from_slice(&(aad.len() as u64).to_be_bytes());
+    signed
+}
+
+// HMAC state keyed once per algorithm and cloned for every signature.
+//
+// Keying an HMAC hashes the secret into its inner and outer pads, so
+// reusing the keyed state saves that work when many tokens are signed or
+// verified with the same secret. Only the keyed state is kept, not the
+// secret itself.
+#[derive(Clone)]
+pub(crate) struct KeyedMac {
+    hs256: HmacSha256,
+    hs384: HmacSha384,
+    hs512: HmacSha512,
+}
+
+impl KeyedMac {
+    pub(crate) fn new(secret: &[u8]) -> Result<Self, TokenError> {
+        check_secret(secret)?;
+        KeyedMac::new_unchecked(secret)
+    }
+
+    // Keys the MAC without rejecting secrets shorter than `MIN_SECRET_LEN`.
+    pub(crate) fn new_unchecked(secret: &[u8]) -> Result<Self, TokenError> {
+        Ok(KeyedMac {
+            hs256: <HmacSha256 as Mac>::new_from_slice(secret)?,
+            hs384: <HmacSha384 as Mac>::new_from_slice(secret)?,
+            hs512: <HmacSha512 as Mac>::new_from_slice(secret)?,
+        })
+    }
+
+    // Signs the payload with a copy of the keyed state for `alg`.
+    pub(crate) fn sign(&self, alg: Algorithm, payload: &[u8]) -> Vec<u8> {
+        match alg {
+            Algorithm::Hs256 => finalize(&self.hs256, payload),
+            Algorithm::Hs384 => finalize(&self.hs384, payload),
+            Algorithm::Hs512 => finalize(&self.hs512, payload),
+        }
+    }
+}
+
+impl SignatureVerifier for KeyedMac {
+    fn verify(
+        &self,
+        alg: Algorithm,
+        signing_input: &[u8],
+        signature: &[u8],
+    ) -> Result<(), TokenError> {
+        let verified = match alg {
+            Algorithm::Hs256 => verify_slice(&self.hs256, signing_input, signature),
+            Algorithm::Hs384 => verify_slice(&self.hs384, signing_input, signature),
+            Algorithm::Hs512 => verify_slice(&self.hs512, signing_input, signature),
+        };
+        if !verified {
+            return Err(TokenErrorKind::InvalidSignature.into());
+        }
+        Ok(())
+    }
+}
+
+// A hash function that selects the HMAC algorithm at compile time.
+//
+// `TypedSigner` and `TypedVerifier` take the hash as a type parameter, so
+// the algorithm is fixed when the code is compiled instead of matched on
+// for every token. Implemented for `Sha256`, `Sha384` and `Sha512`, which
+// sign as `Algorithm::Hs256`, `Hs384` and `Hs512` respectively.
+pub trait HmacDigest: private::Sealed {
+    // The algorithm recorded in the header of tokens signed with the hash.
+    const ALG: Algorithm;
+
+    // The HMAC built on the hash.
+    #[doc(hidden)]
+    type Mac: Mac + KeyInit + Clone;
+}
+
+impl HmacDigest for Sha256 {
+    const ALG: Algorithm = Algorithm::Hs256;
+    type Mac = HmacSha256;
+}
+
+impl HmacDigest for Sha384 {
+    const ALG: Algorithm = Algorithm::Hs384;
+    type Mac = HmacSha384;
+}
+
+impl HmacDigest for Sha512 {
+    const ALG: Algorithm = Algorithm::Hs512;
+    type Mac = HmacSha512;
+}
+
+// Keeps `HmacDigest` from being implemented outside the crate, since every
+// hash must map to an algorithm verifiers know.
+mod private {
+    pub trait Sealed {}
+
+    impl Sealed for sha2::Sha256 {}
+    impl Sealed for sha2::Sha384 {}
+    impl Sealed for sha2::Sha512 {}
+}
+
+// Computes the MAC of the payload using a clone of the keyed state.
+pub(crate) fn finalize<M>(mac: &M, payload: &[u8]) -> Vec<u8>
+where
+    M: Mac + Clone,
+{
+    let mut mac = mac.clone();
+    mac.update(payload);
+    mac.finalize().into_bytes().to_vec()
+}
+
+// Checks the signature in constant time with `Mac::verify_slice`, using a
+// clone of the keyed state.
+pub(crate) fn verify_slice<M>(mac: &M, payload: &[u8], signature: &[u8]) -> bool
+where
+    M: Mac + Clone,
+{
+    let mut mac = mac.clone();
+    mac.update(payload);
+    mac.verify_slice(signature).is_ok()
+}
+
+// Compares two byte strings in constant time.
+//
+// The time taken depends only on the lengths, not on where the bytes
+// differ, so comparing a secret value such as an API key or a signature
+// does not reveal how much of it an attacker guessed right. Slices of
+// different lengths are never equal; their lengths are not hidden.
+//
+// # Arguments
+//
+// * `a` - The first byte string.
+// * `b` - The second byte string.
+//
+// # Returns
+//
+// * `bool` - Whether both byte strings are equal.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.ct_eq(b).into()
+}
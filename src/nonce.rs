@@ -0,0 +1,109 @@
+use alloc::format;
+use alloc::string::String;
+#[cfg(feature = "std")]
+use alloc::string::ToString;
+#[cfg(feature = "std")]
+use core::hash::{BuildHasher, Hasher};
+#[cfg(feature = "std")]
+use core::sync::atomic::{AtomicU64, Ordering};
+#[cfg(feature = "std")]
+use std::collections::hash_map::RandomState;
+#[cfg(feature = "std")]
+use std::collections::HashSet;
+
+// A record of the token ids that have already been used.
+//
+// `verify_token_once` uses the store to accept each single-use token only
+// once. Implement this trait to keep the ids in persistent or shared
+// storage, so they survive restarts and are seen by every server.
+pub trait NonceStore {
+    // Records the token id as used.
+    //
+    // Returns `true` if the id had not been used before, or `false` if it
+    // has already been recorded and the token must be rejected. The check
+    // and the insert must happen atomically, or two concurrent requests
+    // could both consume the same token.
+    fn check_and_insert(&mut self, jti: &str) -> bool;
+}
+
+// An in-memory store. Ids are kept until the set is cleared, so callers
+// should prune or replace it once the tokens it tracks have expired.
+#[cfg(feature = "std")]
+impl NonceStore for HashSet<String> {
+    fn check_and_insert(&mut self, jti: &str) -> bool {
+        self.insert(jti.to_string())
+    }
+}
+
+// A source of the token ids (`jti`) the crate generates.
+//
+// Ids come from `RandomIds` by default. Tests that assert on token
+// contents can pass `SeededIds` instead, so the same seed always yields
+// the same ids. Implement this trait to take ids from elsewhere, such as
+// a UUID generator.
+pub trait IdSource {
+    // Returns a new id, different from every id returned before.
+    fn next_id(&mut self) -> String;
+}
+
+// Unique 128-bit ids from the standard library's randomly seeded hasher.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RandomIds;
+
+#[cfg(feature = "std")]
+impl IdSource for RandomIds {
+    fn next_id(&mut self) -> String {
+        unique_id()
+    }
+}
+
+// Deterministic 128-bit ids generated from a seed, for tests.
+//
+// The ids are as easy to predict as the seed, so never use this outside
+// of tests.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SeededIds {
+    state: u64,
+}
+
+impl SeededIds {
+    // Creates a source whose ids are determined by `seed`.
+    pub fn new(seed: u64) -> Self {
+        SeededIds { state: seed }
+    }
+
+    // Returns the next 64 bits of the SplitMix64 sequence.
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
+
+impl IdSource for SeededIds {
+    fn next_id(&mut self) -> String {
+        format!("{:016x}{:016x}", self.next_u64(), self.next_u64())
+    }
+}
+
+// Returns a new 128-bit id as hex, unique across calls and processes.
+//
+// The ids come from the standard library's randomly seeded hasher, which
+// is enough to tell tokens apart. They are not secret and are not meant to
+// be unguessable.
+#[cfg(feature = "std")]
+pub(crate) fn unique_id() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+
+    let mut halves = [0; 2];
+    for half in &mut halves {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u64(count);
+        *half = hasher.finish();
+    }
+    format!("{:016x}{:016x}", halves[0], halves[1])
+}
@@ -0,0 +1,187 @@
+use crate::validation::duration_secs;
+use crate::{
+    create_token_with_options, verify_with_clock, Claims, Clock, IdSource, NonceStore, RandomIds,
+    SystemClock, Token, TokenError, TokenErrorKind, TokenOptions, Validation,
+};
+use alloc::string::ToString;
+use core::time::Duration;
+
+// The `typ` of the access tokens created by `create_pair` and `rotate`.
+pub const ACCESS_TOKEN_TYPE: &str = "access";
+
+// The `typ` of the refresh tokens created by `create_pair` and `rotate`.
+pub const REFRESH_TOKEN_TYPE: &str = "refresh";
+
+// A short-lived access token and the long-lived refresh token used to
+// replace it once it expires.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenPair {
+    // The access token, presented on every request.
+    pub access_token: Token,
+    // The refresh token, presented only to `rotate` for a new pair.
+    pub refresh_token: Token,
+}
+
+// Creates an access token and a refresh token for the claims.
+//
+// Both tokens carry the claims with `iat` set to now and `exp` set to now
+// plus their time to live. They are tagged with the `ACCESS_TOKEN_TYPE`
+// and `REFRESH_TOKEN_TYPE` types, so a refresh token is never accepted as
+// an access token: verify access tokens with `Validation::expected_typ`
+// set to `ACCESS_TOKEN_TYPE`. The refresh token gets a fresh `jti` so
+// `rotate` can tell when it is used twice.
+//
+// # Arguments
+//
+// * `claims` - The claims to put in both tokens. Any `exp`, `iat` and `jti` are replaced.
+// * `secret` - The secret key used to sign the tokens, as a string or raw bytes.
+// * `access_ttl` - How long the access token is valid for.
+// * `refresh_ttl` - How long the refresh token is valid for.
+//
+// # Returns
+//
+// * `Ok(TokenPair)` - The generated tokens.
+// * `Err(TokenError)` - Any error that occurs during the token creation process.
+pub fn create_pair(
+    claims: &Claims,
+    secret: impl AsRef<[u8]>,
+    access_ttl: Duration,
+    refresh_ttl: Duration,
+) -> Result<TokenPair, TokenError> {
+    create_pair_with(claims, secret, access_ttl, refresh_ttl, &SystemClock, &mut RandomIds)
+}
+
+// Creates a token pair like `create_pair`, reading the time from `clock`
+// and taking the refresh token's `jti` from `ids`.
+//
+// With a `FixedClock` and `SeededIds` the same inputs always produce the
+// same tokens, which keeps snapshot tests stable.
+//
+// # Arguments
+//
+// * `claims` - The claims to put in both tokens. Any `exp`, `iat` and `jti` are replaced.
+// * `secret` - The secret key used to sign the tokens, as a string or raw bytes.
+// * `access_ttl` - How long the access token is valid for.
+// * `refresh_ttl` - How long the refresh token is valid for.
+// * `clock` - The source of the current time.
+// * `ids` - The source of the refresh token's id.
+//
+// # Returns
+//
+// * `Ok(TokenPair)` - The generated tokens.
+// * `Err(TokenError)` - Any error that occurs during the token creation process.
+pub fn create_pair_with(
+    claims: &Claims,
+    secret: impl AsRef<[u8]>,
+    access_ttl: Duration,
+    refresh_ttl: Duration,
+    clock: &impl Clock,
+    ids: &mut impl IdSource,
+) -> Result<TokenPair, TokenError> {
+    let secret = secret.as_ref();
+    let now = clock.now();
+
+    let mut access = claims.clone();
+    access.iat = Some(now);
+    access.exp = now.saturating_add(duration_secs(access_ttl));
+    access.jti = None;
+
+    let mut refresh = claims.clone();
+    refresh.iat = Some(now);
+    refresh.exp = now.saturating_add(duration_secs(refresh_ttl));
+    refresh.jti = Some(ids.next_id());
+
+    Ok(TokenPair {
+        access_token: create_token_with_options(&access, secret, &typed(ACCESS_TOKEN_TYPE))?,
+        refresh_token: create_token_with_options(&refresh, secret, &typed(REFRESH_TOKEN_TYPE))?,
+    })
+}
+
+// Exchanges a refresh token for a new token pair.
+//
+// The refresh token is verified like any other token and must have the
+// `REFRESH_TOKEN_TYPE` type. Once the new pair has been created, its `jti`
+// is recorded in `used`, so each refresh token can be exchanged only once:
+// presenting it again, as an attacker replaying a stolen token would, fails
+// with `AlreadyUsed`. The new pair carries the same claims as the refresh
+// token.
+//
+// # Arguments
+//
+// * `refresh_token` - The refresh token to exchange.
+// * `secret` - The secret key used to verify and sign the tokens, as a string or raw bytes.
+// * `access_ttl` - How long the new access token is valid for.
+// * `refresh_ttl` - How long the new refresh token is valid for.
+// * `used` - The ids of the refresh tokens that have already been exchanged.
+//
+// # Returns
+//
+// * `Ok(TokenPair)` - The new tokens.
+// * `Err(TokenError)` - The refresh token is invalid, expired or already used.
+pub fn rotate<N>(
+    refresh_token: &str,
+    secret: impl AsRef<[u8]>,
+    access_ttl: Duration,
+    refresh_ttl: Duration,
+    used: &mut N,
+) -> Result<TokenPair, TokenError>
+where
+    N: NonceStore + ?Sized,
+{
+    rotate_with(refresh_token, secret, access_ttl, refresh_ttl, used, &SystemClock, &mut RandomIds)
+}
+
+// Exchanges a refresh token like `rotate`, reading the time from `clock`
+// and taking the new refresh token's `jti` from `ids`.
+//
+// # Arguments
+//
+// * `refresh_token` - The refresh token to exchange.
+// * `secret` - The secret key used to verify and sign the tokens, as a string or raw bytes.
+// * `access_ttl` - How long the new access token is valid for.
+// * `refresh_ttl` - How long the new refresh token is valid for.
+// * `used` - The ids of the refresh tokens that have already been exchanged.
+// * `clock` - The source of the current time.
+// * `ids` - The source of the new refresh token's id.
+//
+// # Returns
+//
+// * `Ok(TokenPair)` - The new tokens.
+// * `Err(TokenError)` - The refresh token is invalid, expired or already used.
+pub fn rotate_with<N>(
+    refresh_token: &str,
+    secret: impl AsRef<[u8]>,
+    access_ttl: Duration,
+    refresh_ttl: Duration,
+    used: &mut N,
+    clock: &impl Clock,
+    ids: &mut impl IdSource,
+) -> Result<TokenPair, TokenError>
+where
+    N: NonceStore + ?Sized,
+{
+    let secret = secret.as_ref();
+    let validation = Validation {
+        expected_typ: Some(REFRESH_TOKEN_TYPE.to_string()),
+        ..Validation::default()
+    };
+    let claims: Claims = verify_with_clock(secret, refresh_token, &validation, clock)?;
+
+    let jti = claims.jti.as_deref().ok_or(TokenErrorKind::MissingTokenId)?;
+
+    // Mint the new pair before spending the old refresh token, so a failure
+    // here leaves the caller able to retry with it.
+    let pair = create_pair_with(&claims, secret, access_ttl, refresh_ttl, clock, ids)?;
+    if !used.check_and_insert(jti) {
+        return Err(TokenErrorKind::AlreadyUsed.into());
+    }
+    Ok(pair)
+}
+
+// Returns token options tagging the token with `typ`.
+fn typed(typ: &str) -> TokenOptions {
+    TokenOptions {
+        typ: Some(typ.to_string()),
+        ..TokenOptions::default()
+    }
+}
@@ -0,0 +1,109 @@
+#[cfg(feature = "std")]
+use crate::{
+    now_millis, unverified_header, verify_token_inner, Expirable, TokenError, TokenErrorKind,
+    Validation,
+};
+use crate::{Header, SecretKey};
+use alloc::boxed::Box;
+use core::future::Future;
+use core::pin::Pin;
+#[cfg(feature = "std")]
+use serde::Deserialize;
+
+// A source of the secret each token is verified with.
+//
+// The provider is given the token's header, which is not yet verified,
+// and returns the secret for it, typically looked up by its key id. This
+// lets a multi-tenant server keep a key per tenant in a cache or a KMS.
+// A `SecretKey` is a provider that returns itself for every token.
+pub trait KeyProvider {
+    // Returns the secret for a token with this header, or `None` if the
+    // token's key is unknown.
+    fn key_for(&self, header: &Header) -> Option<SecretKey>;
+}
+
+impl KeyProvider for SecretKey {
+    fn key_for(&self, _header: &Header) -> Option<SecretKey> {
+        Some(self.clone())
+    }
+}
+
+// The future returned by `AsyncKeyProvider::key_for`.
+pub type KeyFuture<'a> = Pin<Box<dyn Future<Output = Option<SecretKey>> + Send + 'a>>;
+
+// A `KeyProvider` whose lookups are asynchronous, such as calls to a
+// remote KMS. The future is boxed so providers can be used as trait
+// objects.
+pub trait AsyncKeyProvider {
+    // Resolves to the secret for a token with this header, or `None` if
+    // the token's key is unknown.
+    fn key_for<'a>(&'a self, header: &'a Header) -> KeyFuture<'a>;
+}
+
+impl AsyncKeyProvider for SecretKey {
+    fn key_for<'a>(&'a self, _header: &'a Header) -> KeyFuture<'a> {
+        Box::pin(core::future::ready(Some(self.clone())))
+    }
+}
+
+// Verifies a token with the secret the provider returns for it.
+//
+// The token's header is decoded and passed to the provider, and the token
+// is then verified like `verify` with the returned secret.
+//
+// # Arguments
+//
+// * `provider` - Returns the secret to verify the token with.
+// * `token` - The token string to be verified and decoded.
+// * `validation` - The checks to run on the token.
+//
+// # Returns
+//
+// * `Ok(T)` - The deserialized payload if the token is valid.
+// * `Err(TokenError)` - `UnknownKeyId` if the provider has no secret for the token, or any
+//   other error that occurs during the verification process.
+#[cfg(feature = "std")]
+pub fn verify_with_provider<T, P>(
+    provider: &P,
+    token: &str,
+    validation: &Validation,
+) -> Result<T, TokenError>
+where
+    T: for<'de> Deserialize<'de> + Expirable,
+    P: KeyProvider + ?Sized,
+{
+    let header = unverified_header(token, validation)?;
+    let secret = provider.key_for(&header).ok_or(TokenErrorKind::UnknownKeyId)?;
+    verify_token_inner(&[secret], token, validation, now_millis())
+}
+
+// Verifies a token with the secret the asynchronous provider resolves for it.
+//
+// This is `verify_with_provider` for providers that look secrets up
+// asynchronously.
+//
+// # Arguments
+//
+// * `provider` - Resolves to the secret to verify the token with.
+// * `token` - The token string to be verified and decoded.
+// * `validation` - The checks to run on the token.
+//
+// # Returns
+//
+// * `Ok(T)` - The deserialized payload if the token is valid.
+// * `Err(TokenError)` - `UnknownKeyId` if the provider has no secret for the token, or any
+//   other error that occurs during the verification process.
+#[cfg(feature = "std")]
+pub async fn verify_with_provider_async<T, P>(
+    provider: &P,
+    token: &str,
+    validation: &Validation,
+) -> Result<T, TokenError>
+where
+    T: for<'de> Deserialize<'de> + Expirable,
+    P: AsyncKeyProvider + ?Sized,
+{
+    let header = unverified_header(token, validation)?;
+    let secret = provider.key_for(&header).await.ok_or(TokenErrorKind::UnknownKeyId)?;
+    verify_token_inner(&[secret], token, validation, now_millis())
+}
@@ -0,0 +1,198 @@
+use crate::{TokenError, TokenErrorKind};
+use alloc::string::String;
+use alloc::vec::Vec;
+use serde_json::Value;
+
+// A lightweight schema for the claims of a payload, set as
+// `Validation::schema`.
+//
+// Each claim listed in the schema has a type and, optionally, a constraint
+// on its value. Required claims must be present and not null, and optional
+// ones are only checked when they are. Claims not listed are accepted as
+// they are, so a contract shared by several issuers only needs to name
+// the claims it relies on. Claims missing from the payload fail with
+// `MissingClaim`, and claims of the wrong type or with a value outside
+// their constraint fail with `InvalidClaim`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ClaimsSchema {
+    claims: Vec<ClaimRule>,
+}
+
+impl ClaimsSchema {
+    // Creates a schema accepting any claims.
+    pub fn new() -> Self {
+        ClaimsSchema::default()
+    }
+
+    // Adds a rule, replacing any rule for the same claim.
+    pub fn claim(mut self, rule: ClaimRule) -> Self {
+        self.claims.retain(|existing| existing.name != rule.name);
+        self.claims.push(rule);
+        self
+    }
+
+    // Returns the names of the claims listed in the schema, in the order
+    // they were added.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.claims.iter().map(|rule| rule.name.as_str())
+    }
+
+    // Returns the rules of the schema, in the order they were added.
+    pub fn rules(&self) -> &[ClaimRule] {
+        &self.claims
+    }
+
+    // Checks decoded claims against the schema.
+    //
+    // # Arguments
+    //
+    // * `claims` - The decoded payload, such as one read with `decode_token`.
+    //
+    // # Returns
+    //
+    // * `Ok(())` - The claims conform to the schema.
+    // * `Err(TokenError)` - `MissingClaim` or `InvalidClaim` for the first rule the claims
+    //   break, in the order the rules were added.
+    pub fn validate(&self, claims: &Value) -> Result<(), TokenError> {
+        match self.failures(claims).next() {
+            Some(kind) => Err(kind.into()),
+            None => Ok(()),
+        }
+    }
+
+    // Returns a failure for every rule the claims break, in the order the
+    // rules were added.
+    pub(crate) fn failures<'a>(
+        &'a self,
+        claims: &'a Value,
+    ) -> impl Iterator<Item = TokenErrorKind> + 'a {
+        self.claims.iter().filter_map(|rule| {
+            match claims.get(&rule.name).filter(|value| !value.is_null()) {
+                Some(value) if !rule.allows(value) => {
+                    Some(TokenErrorKind::InvalidClaim(rule.name.clone()))
+                }
+                None if rule.required => Some(TokenErrorKind::MissingClaim(rule.name.clone())),
+                _ => None,
+            }
+        })
+    }
+}
+
+// The rule a `ClaimsSchema` checks a claim with.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClaimRule {
+    // The name of the claim.
+    pub name: String,
+    // The type of its value.
+    pub kind: ClaimType,
+    // Whether the claim must be present and not null.
+    pub required: bool,
+    // The values accepted, if not any value of its type.
+    pub constraint: Option<ClaimConstraint>,
+}
+
+impl ClaimRule {
+    // Creates a rule for a claim that must be present, with any value of
+    // the given type.
+    pub fn required(name: impl Into<String>, kind: ClaimType) -> Self {
+        ClaimRule {
+            name: name.into(),
+            kind,
+            required: true,
+            constraint: None,
+        }
+    }
+
+    // Creates a rule for a claim that may be missing or null, with any
+    // value of the given type otherwise.
+    pub fn optional(name: impl Into<String>, kind: ClaimType) -> Self {
+        ClaimRule {
+            required: false,
+            ..ClaimRule::required(name, kind)
+        }
+    }
+
+    // Restricts the values the claim accepts.
+    pub fn constraint(mut self, constraint: ClaimConstraint) -> Self {
+        self.constraint = Some(constraint);
+        self
+    }
+
+    // Returns whether a present value has the rule's type and satisfies
+    // its constraint.
+    fn allows(&self, value: &Value) -> bool {
+        self.kind.matches(value)
+            && self.constraint.as_ref().is_none_or(|constraint| constraint.allows(value))
+    }
+}
+
+// The type of a claim's value, as encoded in the payload.
+//
+// The payload is checked as it decodes into a `serde_json::Value`, so
+// payloads with MessagePack binary data or extension types fail to decode
+// when a schema is set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClaimType {
+    // Any value.
+    Any,
+    // `true` or `false`.
+    Boolean,
+    // A whole number, signed or unsigned.
+    Integer,
+    // Any number, whole or not.
+    Number,
+    // A string.
+    String,
+    // An array, or a list in JSON.
+    Array,
+    // A map, or an object in JSON.
+    Map,
+}
+
+impl ClaimType {
+    // Returns whether the value is of this type.
+    fn matches(self, value: &Value) -> bool {
+        match self {
+            ClaimType::Any => true,
+            ClaimType::Boolean => value.is_boolean(),
+            ClaimType::Integer => value.is_i64() || value.is_u64(),
+            ClaimType::Number => value.is_number(),
+            ClaimType::String => value.is_string(),
+            ClaimType::Array => value.is_array(),
+            ClaimType::Map => value.is_object(),
+        }
+    }
+}
+
+// A constraint on the value of a claim, checked after its type.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClaimConstraint {
+    // The value must equal one of these.
+    OneOf(Vec<Value>),
+    // The value must be an integer between `min` and `max`, inclusive.
+    Range { min: i64, max: i64 },
+    // The value must be a string of at most this many characters, or an
+    // array or map of at most this many entries.
+    MaxLen(usize),
+}
+
+impl ClaimConstraint {
+    // Returns whether the value satisfies the constraint.
+    fn allows(&self, value: &Value) -> bool {
+        match self {
+            ClaimConstraint::OneOf(values) => values.contains(value),
+            ClaimConstraint::Range { min, max } => {
+                value.as_i64().is_some_and(|value| (*min..=*max).contains(&value))
+            }
+            ClaimConstraint::MaxLen(max_len) => {
+                let len = match value {
+                    Value::String(value) => value.chars().count(),
+                    Value::Array(values) => values.len(),
+                    Value::Object(entries) => entries.len(),
+                    _ => return false,
+                };
+                len <= *max_len
+            }
+        }
+    }
+}
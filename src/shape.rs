@@ -0,0 +1,156 @@
+#[cfg(feature = "msgpack")]
+use alloc::string::{String, ToString};
+#[cfg(feature = "msgpack")]
+use serde::de::{self, Deserialize, Deserializer, Expected, Visitor};
+
+// Describes the top-level value of a MessagePack payload from its first
+// byte, as named in `PayloadTypeMismatch` errors. Maps and arrays, which
+// structs and collections are read from, give `None`.
+pub(crate) fn msgpack_scalar(bytes: &[u8]) -> Option<&'static str> {
+    let shape = match *bytes.first()? {
+        0x00..=0x7f | 0xcc..=0xd3 | 0xe0..=0xff => "an integer",
+        0xa0..=0xbf | 0xd9..=0xdb => "a string",
+        0xc0 => "nil",
+        0xc2 | 0xc3 => "a boolean",
+        0xc4..=0xc6 => "binary data",
+        0xca | 0xcb => "a float",
+        0xc7..=0xc9 | 0xd4..=0xd8 => "an extension",
+        0x80..=0x9f | 0xc1 | 0xdc..=0xdf => return None,
+    };
+    Some(shape)
+}
+
+// Describes the top-level value of a MessagePack payload from its first
+// byte unless it is a map, such as the array a struct created with
+// `named_fields` off is written as.
+pub(crate) fn msgpack_non_map(bytes: &[u8]) -> Option<&'static str> {
+    match *bytes.first()? {
+        0x80..=0x8f | 0xde | 0xdf => None,
+        0x90..=0x9f | 0xdc | 0xdd => Some("an array"),
+        _ => msgpack_scalar(bytes),
+    }
+}
+
+// Describes the top-level value of a JSON payload unless it is an object.
+// Malformed JSON gives `None`, and is left for serde_json to reject.
+pub(crate) fn json_non_map(bytes: &[u8]) -> Option<&'static str> {
+    let first = bytes.iter().find(|byte| !byte.is_ascii_whitespace())?;
+    let shape = match *first {
+        b'[' => "an array",
+        b'"' => "a string",
+        b't' | b'f' => "a boolean",
+        b'n' => "null",
+        b'-' | b'0'..=b'9' => "a number",
+        _ => return None,
+    };
+    Some(shape)
+}
+
+// Returns what `T` expects at the top level, such as `struct Claims`, if
+// it is read from a map or an array. Scalar types give `None`.
+//
+// `T` is deserialized from a probe that fails on the first call, which
+// only records what `T` asked for.
+#[cfg(feature = "msgpack")]
+pub(crate) fn expected_container<T>() -> Option<String>
+where
+    T: for<'de> Deserialize<'de>,
+{
+    match T::deserialize(Probe) {
+        Err(Probed::Container(expected)) => Some(expected),
+        _ => None,
+    }
+}
+
+#[cfg(feature = "msgpack")]
+struct Probe;
+
+#[cfg(feature = "msgpack")]
+#[derive(Debug)]
+enum Probed {
+    Container(String),
+    Other,
+}
+
+#[cfg(feature = "msgpack")]
+impl de::Error for Probed {
+    fn custom<M: core::fmt::Display>(_msg: M) -> Self {
+        Probed::Other
+    }
+}
+
+#[cfg(feature = "msgpack")]
+impl core::fmt::Display for Probed {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("shape probe")
+    }
+}
+
+#[cfg(feature = "msgpack")]
+impl core::error::Error for Probed {}
+
+#[cfg(feature = "msgpack")]
+fn container<'de, V: Visitor<'de>, R>(visitor: V) -> Result<R, Probed> {
+    Err(Probed::Container((&visitor as &dyn Expected).to_string()))
+}
+
+#[cfg(feature = "msgpack")]
+impl<'de> Deserializer<'de> for Probe {
+    type Error = Probed;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Probed> {
+        Err(Probed::Other)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Probed> {
+        container(visitor)
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Probed> {
+        container(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Probed> {
+        container(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Probed> {
+        container(visitor)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Probed> {
+        container(visitor)
+    }
+
+    // Wrappers are looked through, to what they wrap.
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Probed> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Probed> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+        unit unit_struct enum identifier ignored_any
+    }
+}
@@ -0,0 +1,462 @@
+use crate::key::check_secret;
+use crate::mac::{finalize, verify_slice, HmacDigest, KeyedMac, SignatureVerifier};
+use crate::{
+    encode_token, verify_token_inner, Algorithm, Clock, Expirable, TokenError, TokenErrorKind,
+    Token, TokenOptions, Validation,
+};
+#[cfg(feature = "std")]
+use crate::now_millis;
+use core::cell::Cell;
+use core::fmt;
+use core::marker::PhantomData;
+use hmac::Mac;
+use serde::{Deserialize, Serialize};
+
+// Creates tokens with a secret keyed once up front.
+//
+// `create_token` keys a new HMAC on every call. A `Signer` keys it when it
+// is created and clones the keyed state for each token, which saves that
+// work on servers minting tokens constantly. Only the keyed state is kept,
+// so the secret can be dropped (and wiped, if it is a `SecretKey`) as soon
+// as the signer has been created.
+#[derive(Clone)]
+pub struct Signer {
+    mac: KeyedMac,
+    options: TokenOptions,
+}
+
+impl Signer {
+    // Creates a signer producing tokens like `create_token`.
+    pub fn new(secret: impl AsRef<[u8]>) -> Result<Self, TokenError> {
+        Signer::with_options(secret, TokenOptions::default())
+    }
+
+    // Creates a signer producing tokens like `create_token_with_options`.
+    pub fn with_options(
+        secret: impl AsRef<[u8]>,
+        options: TokenOptions,
+    ) -> Result<Self, TokenError> {
+        Ok(Signer {
+            mac: KeyedMac::new(secret.as_ref())?,
+            options,
+        })
+    }
+
+    // Creates a signer like `with_options` that accepts secrets shorter
+    // than `MIN_SECRET_LEN`.
+    //
+    // Short secrets are rejected everywhere else because they are weak or a
+    // sign of a missing configuration value. Use this only where a short
+    // secret is deliberate, such as fixtures in tests.
+    pub fn new_unchecked(
+        secret: impl AsRef<[u8]>,
+        options: TokenOptions,
+    ) -> Result<Self, TokenError> {
+        Ok(Signer {
+            mac: KeyedMac::new_unchecked(secret.as_ref())?,
+            options,
+        })
+    }
+
+    // Returns the options tokens are created with.
+    pub fn options(&self) -> &TokenOptions {
+        &self.options
+    }
+
+    // Creates a token for the payload.
+    //
+    // # Arguments
+    //
+    // * `payload` - The data to be serialized into the token.
+    //
+    // # Returns
+    //
+    // * `Ok(Token)` - The generated token.
+    // * `Err(TokenError)` - Any error that occurs during the token creation process.
+    pub fn sign<T>(&self, payload: &T) -> Result<Token, TokenError>
+    where
+        T: Serialize,
+    {
+        encode_token(payload, &self.options, |signing_input| {
+            Ok(self.mac.sign(self.options.alg, signing_input))
+        })
+    }
+}
+
+// Never prints the keyed state, which is derived from the secret.
+impl fmt::Debug for Signer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Signer")
+            .field("options", &self.options)
+            .finish_non_exhaustive()
+    }
+}
+
+// Verifies tokens with a secret keyed once up front.
+//
+// This is the verifying counterpart of `Signer`. Each token is checked as
+// in `verify`, against the validation the verifier was created with.
+//
+// To rotate keys, create the verifier with `with_secondary`: tokens are
+// checked against the new primary key first and fall back to the old
+// secondary key only if that fails. `verify_matched` reports which key
+// verified each token, so the old key can be retired once it stops
+// matching.
+#[derive(Clone)]
+pub struct Verifier {
+    mac: KeyedMac,
+    secondary: Option<KeyedMac>,
+    validation: Validation,
+}
+
+// The key of a `Verifier` that verified a token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyMatch {
+    // The primary key, which new tokens are signed with.
+    Primary,
+    // The secondary key, which is being rotated out.
+    Secondary,
+}
+
+impl Verifier {
+    // Creates a verifier checking tokens like `verify_token`.
+    pub fn new(secret: impl AsRef<[u8]>) -> Result<Self, TokenError> {
+        Verifier::with_validation(secret, Validation::default())
+    }
+
+    // Creates a verifier checking tokens like `verify` with `validation`.
+    pub fn with_validation(
+        secret: impl AsRef<[u8]>,
+        validation: Validation,
+    ) -> Result<Self, TokenError> {
+        Ok(Verifier {
+            mac: KeyedMac::new(secret.as_ref())?,
+            secondary: None,
+            validation,
+        })
+    }
+
+    // Creates a verifier accepting tokens signed with either key, trying
+    // `primary` first, and checking them like `verify` with `validation`.
+    pub fn with_secondary(
+        primary: impl AsRef<[u8]>,
+        secondary: impl AsRef<[u8]>,
+        validation: Validation,
+    ) -> Result<Self, TokenError> {
+        Ok(Verifier {
+            mac: KeyedMac::new(primary.as_ref())?,
+            secondary: Some(KeyedMac::new(secondary.as_ref())?),
+            validation,
+        })
+    }
+
+    // Creates a verifier like `with_validation` that accepts secrets
+    // shorter than `MIN_SECRET_LEN`, the counterpart of
+    // `Signer::new_unchecked`.
+    pub fn new_unchecked(
+        secret: impl AsRef<[u8]>,
+        validation: Validation,
+    ) -> Result<Self, TokenError> {
+        Ok(Verifier {
+            mac: KeyedMac::new_unchecked(secret.as_ref())?,
+            secondary: None,
+            validation,
+        })
+    }
+
+    // Returns the checks tokens are verified with.
+    pub fn validation(&self) -> &Validation {
+        &self.validation
+    }
+
+    // Verifies a token and returns the decoded payload if valid.
+    //
+    // # Arguments
+    //
+    // * `token` - The token string to be verified and decoded.
+    //
+    // # Returns
+    //
+    // * `Ok(T)` - The deserialized payload if the token is valid.
+    // * `Err(TokenError)` - Any error that occurs during the verification process.
+    #[cfg(feature = "std")]
+    pub fn verify<T>(&self, token: &str) -> Result<T, TokenError>
+    where
+        T: for<'de> Deserialize<'de> + Expirable,
+    {
+        verify_token_inner(&self.keys(), token, &self.validation, now_millis())
+    }
+
+    // Verifies a token as of the given time (Unix timestamp).
+    pub fn verify_at<T>(&self, token: &str, now: i64) -> Result<T, TokenError>
+    where
+        T: for<'de> Deserialize<'de> + Expirable,
+    {
+        verify_token_inner(&self.keys(), token, &self.validation, now.saturating_mul(1000))
+    }
+
+    // Verifies a token with the current time read from `clock`.
+    pub fn verify_with_clock<T>(&self, token: &str, clock: &impl Clock) -> Result<T, TokenError>
+    where
+        T: for<'de> Deserialize<'de> + Expirable,
+    {
+        verify_token_inner(&self.keys(), token, &self.validation, clock.now_millis())
+    }
+
+    // Verifies a token and reports which key verified it.
+    //
+    // # Arguments
+    //
+    // * `token` - The token string to be verified and decoded.
+    //
+    // # Returns
+    //
+    // * `Ok((T, KeyMatch))` - The deserialized payload and the key that verified it.
+    // * `Err(TokenError)` - Any error that occurs during the verification process.
+    #[cfg(feature = "std")]
+    pub fn verify_matched<T>(&self, token: &str) -> Result<(T, KeyMatch), TokenError>
+    where
+        T: for<'de> Deserialize<'de> + Expirable,
+    {
+        let keys = self.keys();
+        let payload = verify_token_inner(&keys, token, &self.validation, now_millis())?;
+        Ok((payload, keys.matched.get()))
+    }
+
+    // Returns the keys to check a token against.
+    fn keys(&self) -> KeyRing<'_> {
+        KeyRing {
+            primary: &self.mac,
+            secondary: self.secondary.as_ref(),
+            matched: Cell::new(KeyMatch::Primary),
+        }
+    }
+}
+
+// The keys of a `Verifier`, recording which one verified the signature.
+struct KeyRing<'a> {
+    primary: &'a KeyedMac,
+    secondary: Option<&'a KeyedMac>,
+    matched: Cell<KeyMatch>,
+}
+
+impl SignatureVerifier for KeyRing<'_> {
+    fn verify(
+        &self,
+        alg: Algorithm,
+        signing_input: &[u8],
+        signature: &[u8],
+    ) -> Result<(), TokenError> {
+        let result = self.primary.verify(alg, signing_input, signature);
+        match (result, self.secondary) {
+            (Err(err), Some(secondary)) if *err.kind() == TokenErrorKind::InvalidSignature => {
+                secondary.verify(alg, signing_input, signature)?;
+                self.matched.set(KeyMatch::Secondary);
+                Ok(())
+            }
+            (result, _) => result,
+        }
+    }
+}
+
+// Never prints the keyed state, which is derived from the secret.
+impl fmt::Debug for Verifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Verifier")
+            .field("validation", &self.validation)
+            .finish_non_exhaustive()
+    }
+}
+
+// A `Signer` whose algorithm is fixed at compile time by the hash `D`.
+//
+// Tokens are signed with the HMAC of `D` without matching on the
+// algorithm, and record it in their header like any other token, so
+// `verify_token` and `Verifier` accept them as usual.
+pub struct TypedSigner<D: HmacDigest> {
+    mac: D::Mac,
+    options: TokenOptions,
+}
+
+impl<D: HmacDigest> TypedSigner<D> {
+    // Creates a signer producing tokens like `create_token_with_alg`.
+    pub fn new(secret: impl AsRef<[u8]>) -> Result<Self, TokenError> {
+        TypedSigner::with_options(secret, TokenOptions::default())
+    }
+
+    // Creates a signer producing tokens like `create_token_with_options`.
+    // The algorithm in `options` is replaced with the one of `D`.
+    pub fn with_options(
+        secret: impl AsRef<[u8]>,
+        options: TokenOptions,
+    ) -> Result<Self, TokenError> {
+        let secret = secret.as_ref();
+        check_secret(secret)?;
+        Ok(TypedSigner {
+            mac: <D::Mac as Mac>::new_from_slice(secret)?,
+            options: TokenOptions {
+                alg: D::ALG,
+                ..options
+            },
+        })
+    }
+
+    // Returns the options tokens are created with.
+    pub fn options(&self) -> &TokenOptions {
+        &self.options
+    }
+
+    // Creates a token for the payload.
+    //
+    // # Arguments
+    //
+    // * `payload` - The data to be serialized into the token.
+    //
+    // # Returns
+    //
+    // * `Ok(Token)` - The generated token.
+    // * `Err(TokenError)` - Any error that occurs during the token creation process.
+    pub fn sign<T>(&self, payload: &T) -> Result<Token, TokenError>
+    where
+        T: Serialize,
+    {
+        encode_token(payload, &self.options, |signing_input| {
+            Ok(finalize(&self.mac, signing_input))
+        })
+    }
+}
+
+impl<D: HmacDigest> Clone for TypedSigner<D> {
+    fn clone(&self) -> Self {
+        TypedSigner {
+            mac: self.mac.clone(),
+            options: self.options.clone(),
+        }
+    }
+}
+
+// Never prints the keyed state, which is derived from the secret.
+impl<D: HmacDigest> fmt::Debug for TypedSigner<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TypedSigner")
+            .field("options", &self.options)
+            .finish_non_exhaustive()
+    }
+}
+
+// A `Verifier` whose algorithm is fixed at compile time by the hash `D`.
+//
+// Only tokens signed with the HMAC of `D` are accepted; any other
+// algorithm fails with `AlgorithmMismatch` before the signature is
+// checked. Tokens must also pass the validation's algorithm allowlist.
+pub struct TypedVerifier<D: HmacDigest> {
+    mac: TypedMac<D>,
+    validation: Validation,
+}
+
+impl<D: HmacDigest> TypedVerifier<D> {
+    // Creates a verifier checking tokens like `verify_token_with_alg`.
+    pub fn new(secret: impl AsRef<[u8]>) -> Result<Self, TokenError> {
+        TypedVerifier::with_validation(secret, Validation::default())
+    }
+
+    // Creates a verifier checking tokens like `verify` with `validation`.
+    pub fn with_validation(
+        secret: impl AsRef<[u8]>,
+        validation: Validation,
+    ) -> Result<Self, TokenError> {
+        let secret = secret.as_ref();
+        check_secret(secret)?;
+        Ok(TypedVerifier {
+            mac: TypedMac {
+                mac: <D::Mac as Mac>::new_from_slice(secret)?,
+                digest: PhantomData,
+            },
+            validation,
+        })
+    }
+
+    // Returns the checks tokens are verified with.
+    pub fn validation(&self) -> &Validation {
+        &self.validation
+    }
+
+    // Verifies a token and returns the decoded payload if valid.
+    //
+    // # Arguments
+    //
+    // * `token` - The token string to be verified and decoded.
+    //
+    // # Returns
+    //
+    // * `Ok(T)` - The deserialized payload if the token is valid.
+    // * `Err(TokenError)` - Any error that occurs during the verification process.
+    #[cfg(feature = "std")]
+    pub fn verify<T>(&self, token: &str) -> Result<T, TokenError>
+    where
+        T: for<'de> Deserialize<'de> + Expirable,
+    {
+        verify_token_inner(&self.mac, token, &self.validation, now_millis())
+    }
+
+    // Verifies a token as of the given time (Unix timestamp).
+    pub fn verify_at<T>(&self, token: &str, now: i64) -> Result<T, TokenError>
+    where
+        T: for<'de> Deserialize<'de> + Expirable,
+    {
+        verify_token_inner(&self.mac, token, &self.validation, now.saturating_mul(1000))
+    }
+
+    // Verifies a token with the current time read from `clock`.
+    pub fn verify_with_clock<T>(&self, token: &str, clock: &impl Clock) -> Result<T, TokenError>
+    where
+        T: for<'de> Deserialize<'de> + Expirable,
+    {
+        verify_token_inner(&self.mac, token, &self.validation, clock.now_millis())
+    }
+}
+
+impl<D: HmacDigest> Clone for TypedVerifier<D> {
+    fn clone(&self) -> Self {
+        TypedVerifier {
+            mac: TypedMac {
+                mac: self.mac.mac.clone(),
+                digest: PhantomData,
+            },
+            validation: self.validation.clone(),
+        }
+    }
+}
+
+// Never prints the keyed state, which is derived from the secret.
+impl<D: HmacDigest> fmt::Debug for TypedVerifier<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TypedVerifier")
+            .field("validation", &self.validation)
+            .finish_non_exhaustive()
+    }
+}
+
+// The keyed HMAC of a `TypedVerifier`, accepting only signatures made with
+// the algorithm of `D`.
+struct TypedMac<D: HmacDigest> {
+    mac: D::Mac,
+    digest: PhantomData<D>,
+}
+
+impl<D: HmacDigest> SignatureVerifier for TypedMac<D> {
+    fn verify(
+        &self,
+        alg: Algorithm,
+        signing_input: &[u8],
+        signature: &[u8],
+    ) -> Result<(), TokenError> {
+        if alg != D::ALG {
+            return Err(TokenErrorKind::AlgorithmMismatch.into());
+        }
+        if !verify_slice(&self.mac, signing_input, signature) {
+            return Err(TokenErrorKind::InvalidSignature.into());
+        }
+        Ok(())
+    }
+}
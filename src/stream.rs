@@ -0,0 +1,110 @@
+use crate::key::check_secret;
+use crate::{decode_segment, Algorithm, Encoding, HmacSha256, TokenError, TokenErrorKind};
+use alloc::string::String;
+use base64::Engine;
+use core::fmt;
+use hmac::digest::KeyInit;
+use hmac::Mac;
+
+// Prefixed to the content covered by a detached signature. Token signing
+// inputs are base64 text, so they never contain the NUL byte, and a
+// detached signature can't be passed off as the signature of a token.
+const DETACHED_CONTEXT: &[u8] = b"crabtoken-detached\0";
+
+// Signs content fed to it in chunks, producing a detached signature.
+//
+// This is `sign_detached` for content too large to hold in memory at once,
+// such as a large file: feed it with `update` as it is read, then call
+// `finalize` for the signature. Streamed content has no claims, so there
+// is no expiry to check; put a timestamp in the content, or send one in a
+// token alongside it, if the signature should expire.
+#[derive(Clone)]
+pub struct StreamSigner {
+    mac: HmacSha256,
+}
+
+impl StreamSigner {
+    // Creates a signer for new content.
+    pub fn new(secret: impl AsRef<[u8]>) -> Result<Self, TokenError> {
+        Ok(StreamSigner {
+            mac: detached_mac(secret.as_ref())?,
+        })
+    }
+
+    // Adds the next chunk of the content.
+    pub fn update(&mut self, chunk: &[u8]) {
+        self.mac.update(chunk);
+    }
+
+    // Returns the base64url-encoded signature of the content, as
+    // `sign_detached` would for the chunks joined together.
+    pub fn finalize(self) -> String {
+        Encoding::default().encoder().encode(self.mac.finalize().into_bytes())
+    }
+}
+
+// Never prints the keyed state, which is derived from the secret.
+impl fmt::Debug for StreamSigner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StreamSigner").finish_non_exhaustive()
+    }
+}
+
+// Checks a detached signature for content fed to it in chunks.
+//
+// This is `verify_detached` for content too large to hold in memory at
+// once. As with `StreamSigner`, nothing expires.
+#[derive(Clone)]
+pub struct StreamVerifier {
+    mac: HmacSha256,
+}
+
+impl StreamVerifier {
+    // Creates a verifier for new content.
+    pub fn new(secret: impl AsRef<[u8]>) -> Result<Self, TokenError> {
+        Ok(StreamVerifier {
+            mac: detached_mac(secret.as_ref())?,
+        })
+    }
+
+    // Adds the next chunk of the content.
+    pub fn update(&mut self, chunk: &[u8]) {
+        self.mac.update(chunk);
+    }
+
+    // Checks the signature of the content in constant time.
+    //
+    // # Arguments
+    //
+    // * `signature` - The base64url-encoded signature, with or without padding.
+    //
+    // # Returns
+    //
+    // * `Ok(())` - The signature is valid for the content.
+    // * `Err(TokenError)` - The signature does not match, or any error that occurs while
+    //   checking it.
+    pub fn verify(self, signature: &str) -> Result<(), TokenError> {
+        let signature = decode_segment("signature", signature, Encoding::default())?;
+        if signature.len() != Algorithm::Hs256.signature_len() {
+            return Err(TokenErrorKind::BadSignatureLength.into());
+        }
+        self.mac
+            .verify_slice(&signature)
+            .map_err(|_| TokenErrorKind::InvalidSignature.into())
+    }
+}
+
+// Never prints the keyed state, which is derived from the secret.
+impl fmt::Debug for StreamVerifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StreamVerifier").finish_non_exhaustive()
+    }
+}
+
+// Keys the MAC for a detached signature and feeds it the context prefix.
+fn detached_mac(secret: &[u8]) -> Result<HmacSha256, TokenError> {
+    check_secret(secret)?;
+    let mut mac = <HmacSha256 as KeyInit>::new_from_slice(secret)?;
+    mac.update(DETACHED_CONTEXT);
+    Ok(mac)
+}
@@ -0,0 +1,98 @@
+use crate::{decode_header, fingerprint, parse_token, split_token, Header, TokenError, TokenParts};
+use alloc::string::String;
+use core::fmt;
+use core::ops::Deref;
+use core::str::FromStr;
+use serde::{Deserialize, Serialize};
+
+// A token string, as returned by `create_token` and the other functions
+// creating tokens.
+//
+// Keeping tokens in their own type stops them from being mixed up with
+// other strings, such as the user id they were issued for. A `Token`
+// dereferences to `str`, so `&token` can be passed to `verify_token`,
+// `decode_token` and every other function taking a token. It serializes
+// as the plain token string, and deserializing checks the string like
+// `from_str` does.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Token(String);
+
+impl Token {
+    // Wraps a string built by the crate as a token.
+    pub(crate) fn new(token: String) -> Self {
+        Token(token)
+    }
+
+    // Returns the token string.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    // Returns the token string, consuming the token.
+    pub fn into_string(self) -> String {
+        self.0
+    }
+
+    // Decodes the token's header, see `decode_header`.
+    pub fn header(&self) -> Result<Header, TokenError> {
+        decode_header(&self.0)
+    }
+
+    // Decodes the token's segments, see `parse_token`.
+    pub fn parts(&self) -> Result<TokenParts, TokenError> {
+        parse_token(&self.0)
+    }
+
+    // Returns a short fingerprint of the token, see `fingerprint`.
+    pub fn fingerprint(&self) -> String {
+        fingerprint(&self.0)
+    }
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+// Parses a token, checking only that it has three non-empty segments.
+// Tokens in the original two-segment format are rejected.
+impl FromStr for Token {
+    type Err = TokenError;
+
+    fn from_str(token: &str) -> Result<Self, Self::Err> {
+        split_token(token)?;
+        Ok(Token(token.into()))
+    }
+}
+
+// Checks the string like `from_str`, without copying it.
+impl TryFrom<String> for Token {
+    type Error = TokenError;
+
+    fn try_from(token: String) -> Result<Self, Self::Error> {
+        split_token(&token)?;
+        Ok(Token(token))
+    }
+}
+
+impl Deref for Token {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<str> for Token {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<Token> for String {
+    fn from(token: Token) -> Self {
+        token.0
+    }
+}
@@ -0,0 +1,372 @@
+use crate::{Algorithm, Audience, ClaimsSchema, Encoding, Expirable, TokenError, TokenErrorKind};
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::time::Duration;
+use serde_json::Value;
+
+// The longest token accepted by default, in bytes.
+pub const DEFAULT_MAX_TOKEN_LEN: usize = 8 * 1024;
+
+// The deepest nesting of arrays and maps accepted in a payload by default.
+pub const DEFAULT_MAX_DEPTH: usize = 64;
+
+// Options controlling which checks `verify` runs on a token.
+//
+// The default validation accepts every algorithm and only checks `exp` and
+// `nbf` with no leeway, and the token length, which is exactly what
+// `verify_token` does. New checks are added here as fields whose default
+// leaves them off.
+#[derive(Debug, Clone)]
+pub struct Validation {
+    // The algorithms a token may be signed with, all of them by default.
+    //
+    // The algorithm is read from the token header, which the sender
+    // controls, so pinning it here stops a token from choosing how it is
+    // verified. Tokens signed with any other algorithm fail with
+    // `AlgorithmMismatch` before their signature is checked, and an empty
+    // list rejects every token.
+    pub algorithms: Vec<Algorithm>,
+    // The audiences the token may be intended for, if any.
+    //
+    // The token passes if any of its audiences is one of these: a single
+    // expected audience must be among the token's, and with several, such
+    // as for a gateway fronting many services, the two sets must share at
+    // least one audience. An empty list accepts no token. Tokens without an
+    // `aud` are rejected when this is set.
+    pub expected_aud: Option<Audience>,
+    // The issuer the token must come from, if any.
+    pub expected_iss: Option<String>,
+    // How far past `exp` (or before `nbf` and `iat`) the token is still
+    // accepted. With a zero leeway a token is valid while `nbf <= now < exp`.
+    pub leeway: Duration,
+    // The furthest in the future the token's `exp` may be, if any.
+    //
+    // Catches tokens that effectively never expire, such as an `Expirable`
+    // whose `exp` returns `i64::MAX`, before they reach production. Tokens
+    // expiring later than this from now fail with `LifetimeTooLong`.
+    pub max_lifetime: Option<Duration>,
+    // The maximum time since the token's `iat`, if any. Tokens without an
+    // `iat` are rejected when this is set, since their age is unknown.
+    pub max_age: Option<Duration>,
+    // The lowest token version accepted, if any.
+    //
+    // Tokens carry the version they were issued at, such as `Claims`'s
+    // `version` claim. Raising this invalidates every token issued below
+    // it at once, for example after a breach, without tracking each one.
+    // Tokens without a version are rejected when this is set.
+    pub min_version: Option<u64>,
+    // The type the token must be tagged with, if any. Tokens without a
+    // type are rejected when this is set.
+    pub expected_typ: Option<String>,
+    // The claims the token must have, with a value other than null.
+    //
+    // The payload must be encoded as a map for its claims to be found by
+    // name. Structs are, in JSON and in MessagePack by default, but not
+    // when created with `TokenOptions::named_fields` off, which writes them
+    // as arrays of their fields. `Claims` is a map either way.
+    pub required_claims: Vec<String>,
+    // The scopes the token must grant, as in OAuth 2.0.
+    //
+    // The `scope` claim may be a space-delimited string or a list of
+    // strings, and every scope listed here must appear in it. As with
+    // `required_claims`, the payload must be encoded as a map.
+    pub required_scopes: Vec<String>,
+    // The schema the claims must conform to, if any.
+    //
+    // Checked on the decoded payload after the signature, along with
+    // `required_claims`, so the same caveat about map encoding applies.
+    pub schema: Option<ClaimsSchema>,
+    // The base64 variant tokens are expected to be encoded with.
+    pub encoding: Encoding,
+    // Whether to also accept tokens in the original two-segment format,
+    // which are always HMAC-SHA256 signed MessagePack payloads.
+    pub allow_legacy: bool,
+    // The longest token accepted, in bytes. Longer tokens are rejected
+    // before they are decoded.
+    pub max_token_len: usize,
+    // The deepest nesting of arrays and maps accepted in the payload, so a
+    // malicious payload can't exhaust the stack while it is deserialized.
+    // JSON payloads are also capped at 128 levels by serde_json.
+    pub max_depth: usize,
+    // Whether to check every claim and report all that fail, instead of
+    // stopping at the first.
+    //
+    // Useful when debugging a misconfigured client. Several failures are
+    // reported together as `Multiple`, and a single one as it is. This
+    // covers the required claims and scopes and the schema as well as the
+    // time, version, issuer and audience checks. The signature, and
+    // everything checked before the claims, still fails on its own so
+    // nothing is read from a token that isn't authentic.
+    pub collect_errors: bool,
+}
+
+impl Default for Validation {
+    fn default() -> Self {
+        Validation {
+            algorithms: Algorithm::ALL.to_vec(),
+            expected_aud: None,
+            expected_iss: None,
+            leeway: Duration::ZERO,
+            max_lifetime: None,
+            max_age: None,
+            min_version: None,
+            expected_typ: None,
+            required_claims: Vec::new(),
+            required_scopes: Vec::new(),
+            schema: None,
+            encoding: Encoding::default(),
+            allow_legacy: false,
+            max_token_len: DEFAULT_MAX_TOKEN_LEN,
+            max_depth: DEFAULT_MAX_DEPTH,
+            collect_errors: false,
+        }
+    }
+}
+
+// Checks the claims of a verified payload against the validation.
+//
+// `now_millis` is the current time as a Unix timestamp in milliseconds.
+// Second-based claims are compared against the current whole second.
+// `failures` are those already collected from the decoded payload by
+// `check_payload_claims`, reported first.
+pub(crate) fn validate_claims<T>(
+    payload: &T,
+    validation: &Validation,
+    now_millis: i64,
+    mut failures: Vec<TokenErrorKind>,
+) -> Result<(), TokenError>
+where
+    T: Expirable,
+{
+    let leeway = duration_secs(validation.leeway);
+    let leeway_millis = duration_millis(validation.leeway);
+    let now = now_millis.div_euclid(1000);
+
+    let mut fail = |kind: TokenErrorKind| {
+        if validation.collect_errors {
+            failures.push(kind);
+            Ok(())
+        } else {
+            Err(TokenError::from(kind))
+        }
+    };
+
+    // A token is expired once `now` reaches `exp`, not only after it.
+    let expired = match payload.exp_millis() {
+        Some(exp_millis) => exp_millis.saturating_add(leeway_millis) <= now_millis,
+        None => payload.exp().saturating_add(leeway) <= now,
+    };
+    if expired {
+        fail(TokenErrorKind::Expired)?;
+    }
+
+    if let Some(max_lifetime) = validation.max_lifetime {
+        let too_long = match payload.exp_millis() {
+            Some(exp_millis) => {
+                exp_millis.saturating_sub(now_millis) > duration_millis(max_lifetime)
+            }
+            None => payload.exp().saturating_sub(now) > duration_secs(max_lifetime),
+        };
+        if too_long {
+            fail(TokenErrorKind::LifetimeTooLong)?;
+        }
+    }
+
+    if let Some(nbf) = payload.nbf() {
+        if nbf.saturating_sub(leeway) > now {
+            fail(TokenErrorKind::NotYetValid)?;
+        }
+    }
+
+    if let Some(iat) = payload.iat() {
+        if iat.saturating_sub(leeway) > now {
+            fail(TokenErrorKind::IssuedInFuture)?;
+        }
+    }
+
+    if let Some(max_age) = validation.max_age {
+        let max_age = duration_secs(max_age);
+        let too_old = payload
+            .iat()
+            .is_none_or(|iat| iat.saturating_add(max_age).saturating_add(leeway) < now);
+        if too_old {
+            fail(TokenErrorKind::TooOld)?;
+        }
+    }
+
+    if let Some(min_version) = validation.min_version {
+        if payload.version().is_none_or(|version| version < min_version) {
+            fail(TokenErrorKind::StaleVersion)?;
+        }
+    }
+
+    if let Some(expected_iss) = &validation.expected_iss {
+        if payload.iss() != Some(expected_iss.as_str()) {
+            fail(TokenErrorKind::InvalidIssuer)?;
+        }
+    }
+
+    if let Some(expected_aud) = &validation.expected_aud {
+        if !expected_aud.iter().any(|aud| payload.has_aud(aud)) {
+            fail(TokenErrorKind::InvalidAudience)?;
+        }
+    }
+
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(collected_error(failures))
+    }
+}
+
+// Reports collected failures: a single one as it is, several as `Multiple`.
+pub(crate) fn collected_error(mut failures: Vec<TokenErrorKind>) -> TokenError {
+    if failures.len() == 1 {
+        failures.remove(0).into()
+    } else {
+        TokenErrorKind::Multiple(failures).into()
+    }
+}
+
+// A check run on a token during verification.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Check {
+    // The token was no longer than `max_token_len`.
+    Length,
+    // The token's algorithm was in `algorithms`.
+    Algorithm,
+    // The signature matched.
+    Signature,
+    // The token had the expected type.
+    Type,
+    // The token had every required claim.
+    RequiredClaims,
+    // The token granted every required scope.
+    Scopes,
+    // The token's claims conformed to the schema.
+    Schema,
+    // The token had not expired.
+    Expiration,
+    // The token's `exp` was within `max_lifetime`.
+    Lifetime,
+    // The token's `nbf` had been reached.
+    NotBefore,
+    // The token's `iat` was not in the future.
+    IssuedAt,
+    // The token was younger than `max_age`.
+    MaxAge,
+    // The token's version was at least `min_version`.
+    Version,
+    // The token came from the expected issuer.
+    Issuer,
+    // The token was intended for the expected audience.
+    Audience,
+}
+
+// Returns the checks a token that passed verification went through, in
+// the order they run.
+#[cfg(feature = "std")]
+pub(crate) fn checks_run<T>(payload: &T, validation: &Validation) -> Vec<Check>
+where
+    T: Expirable,
+{
+    let mut checks = alloc::vec![Check::Length, Check::Algorithm, Check::Signature];
+    if validation.expected_typ.is_some() {
+        checks.push(Check::Type);
+    }
+    if !validation.required_claims.is_empty() {
+        checks.push(Check::RequiredClaims);
+    }
+    if !validation.required_scopes.is_empty() {
+        checks.push(Check::Scopes);
+    }
+    if validation.schema.is_some() {
+        checks.push(Check::Schema);
+    }
+    checks.push(Check::Expiration);
+    if validation.max_lifetime.is_some() {
+        checks.push(Check::Lifetime);
+    }
+    if payload.nbf().is_some() {
+        checks.push(Check::NotBefore);
+    }
+    if payload.iat().is_some() {
+        checks.push(Check::IssuedAt);
+    }
+    if validation.max_age.is_some() {
+        checks.push(Check::MaxAge);
+    }
+    if validation.min_version.is_some() {
+        checks.push(Check::Version);
+    }
+    if validation.expected_iss.is_some() {
+        checks.push(Check::Issuer);
+    }
+    if validation.expected_aud.is_some() {
+        checks.push(Check::Audience);
+    }
+    checks
+}
+
+// Checks the decoded payload for the claims and scopes the validation
+// requires, and against its schema.
+//
+// With `collect_errors` every failure is returned, to be reported along
+// with those of `validate_claims`. Otherwise the first fails on its own.
+pub(crate) fn check_payload_claims(
+    claims: &Value,
+    validation: &Validation,
+) -> Result<Vec<TokenErrorKind>, TokenError> {
+    let schema_failures = validation.schema.iter().flat_map(|schema| schema.failures(claims));
+    let mut failures = missing_claims(claims, &validation.required_claims)
+        .chain(missing_scopes(claims, &validation.required_scopes))
+        .chain(schema_failures);
+    if validation.collect_errors {
+        return Ok(failures.collect());
+    }
+    match failures.next() {
+        Some(kind) => Err(kind.into()),
+        None => Ok(Vec::new()),
+    }
+}
+
+// Returns a `MissingClaim` for every required claim that is missing or
+// null in the decoded payload.
+fn missing_claims<'a>(
+    claims: &'a Value,
+    required: &'a [String],
+) -> impl Iterator<Item = TokenErrorKind> + 'a {
+    required
+        .iter()
+        .filter(|name| claims.get(name.as_str()).is_none_or(Value::is_null))
+        .map(|name| TokenErrorKind::MissingClaim(name.clone()))
+}
+
+// Returns a `MissingScope` for every required scope the `scope` claim of
+// the decoded payload doesn't grant, whether it is a space-delimited
+// string or a list.
+fn missing_scopes<'a>(
+    claims: &'a Value,
+    required: &'a [String],
+) -> impl Iterator<Item = TokenErrorKind> + 'a {
+    let granted: Vec<&str> = match claims.get("scope") {
+        Some(Value::String(scope)) => scope.split_whitespace().collect(),
+        Some(Value::Array(scopes)) => scopes.iter().filter_map(Value::as_str).collect(),
+        _ => Vec::new(),
+    };
+    required
+        .iter()
+        .filter(move |name| !granted.contains(&name.as_str()))
+        .map(|name| TokenErrorKind::MissingScope(name.clone()))
+}
+
+// Converts a duration into whole seconds, saturating on overflow.
+pub(crate) fn duration_secs(duration: Duration) -> i64 {
+    i64::try_from(duration.as_secs()).unwrap_or(i64::MAX)
+}
+
+// Converts a duration into whole milliseconds, saturating on overflow.
+fn duration_millis(duration: Duration) -> i64 {
+    i64::try_from(duration.as_millis()).unwrap_or(i64::MAX)
+}
@@ -0,0 +1,115 @@
+#![cfg(feature = "msgpack")]
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use crabtoken::{
+    create_token_with_alg, decode_header, from_jwt, to_jwt, verify, Algorithm, Claims, Sha512,
+    TokenErrorKind, TypedVerifier, Validation,
+};
+
+mod common;
+
+use common::{claims, SECRET};
+
+fn token(alg: Algorithm) -> String {
+    create_token_with_alg(&claims(), SECRET, alg).unwrap().into_string()
+}
+
+fn pinned(alg: Algorithm) -> Validation {
+    Validation {
+        algorithms: vec![alg],
+        ..Validation::default()
+    }
+}
+
+// A verifier pinned to HS512 is handed an HS256 token signed with the
+// very same secret. The signature is valid for the algorithm the token
+// names, so only the pin stops the token from choosing how it is checked.
+#[test]
+fn rejects_a_validly_signed_token_declaring_another_algorithm() {
+    let token = token(Algorithm::Hs256);
+    assert_eq!(decode_header(&token).unwrap().alg, Algorithm::Hs256);
+
+    let err = verify::<Claims>(SECRET, &token, &pinned(Algorithm::Hs512)).unwrap_err();
+    assert_eq!(*err.kind(), TokenErrorKind::AlgorithmMismatch);
+
+    let verifier = TypedVerifier::<Sha512>::new(SECRET).unwrap();
+    let err = verifier.verify::<Claims>(&token).unwrap_err();
+    assert_eq!(*err.kind(), TokenErrorKind::AlgorithmMismatch);
+}
+
+// The header of an HS512 token is swapped for one declaring HS256, as an
+// attacker steering verification to another algorithm would.
+#[test]
+fn rejects_a_token_whose_header_algorithm_was_swapped() {
+    let hs512 = token(Algorithm::Hs512);
+    let hs256 = token(Algorithm::Hs256);
+    let (_, rest) = hs512.split_once('.').unwrap();
+    let (header, _) = hs256.split_once('.').unwrap();
+    let swapped = format!("{}.{}", header, rest);
+
+    let err = verify::<Claims>(SECRET, &swapped, &pinned(Algorithm::Hs512)).unwrap_err();
+    assert_eq!(*err.kind(), TokenErrorKind::AlgorithmMismatch);
+
+    // Without a pin the swap is still caught, since the header is signed.
+    let err = verify::<Claims>(SECRET, &swapped, &Validation::default()).unwrap_err();
+    assert_ne!(*err.kind(), TokenErrorKind::AlgorithmMismatch);
+    assert!(verify::<Claims>(SECRET, &hs512, &pinned(Algorithm::Hs512)).is_ok());
+}
+
+#[test]
+fn empty_allowlist_rejects_every_token() {
+    let validation = Validation {
+        algorithms: Vec::new(),
+        ..Validation::default()
+    };
+    for alg in Algorithm::ALL {
+        let err = verify::<Claims>(SECRET, &token(alg), &validation).unwrap_err();
+        assert_eq!(*err.kind(), TokenErrorKind::AlgorithmMismatch);
+    }
+}
+
+// Replaces the token's header with one declaring `alg`, keeping the other
+// header fields as this crate writes them.
+fn with_alg(token: &str, alg: &str) -> String {
+    let header = serde_json::json!({ "ver": 1, "alg": alg, "fmt": "msgpack" });
+    let header = URL_SAFE_NO_PAD.encode(rmp_serde::to_vec_named(&header).unwrap());
+    let (_, rest) = token.split_once('.').unwrap();
+    format!("{}.{}", header, rest)
+}
+
+#[test]
+fn rejects_tokens_declaring_none() {
+    let token = token(Algorithm::Hs256);
+    // The rewritten header is read like one this crate wrote.
+    let rewritten = with_alg(&token, "HS256");
+    assert_eq!(decode_header(&rewritten).unwrap().alg, Algorithm::Hs256);
+
+    for alg in ["none", "None", "NONE", ""] {
+        let forged = with_alg(&token, alg);
+        let unsupported = TokenErrorKind::UnsupportedAlgorithm(alg.to_string());
+        for validation in [Validation::default(), pinned(Algorithm::Hs256)] {
+            let err = verify::<Claims>(SECRET, &forged, &validation).unwrap_err();
+            assert_eq!(*err.kind(), unsupported);
+        }
+        assert_eq!(*decode_header(&forged).unwrap_err().kind(), unsupported);
+
+        // An unsigned token has no signature at all, which isn't a token.
+        let (unsigned, _) = forged.rsplit_once('.').unwrap();
+        let err = verify::<Claims>(SECRET, &format!("{}.", unsigned), &Validation::default())
+            .unwrap_err();
+        assert_eq!(*err.kind(), TokenErrorKind::InvalidFormat);
+    }
+}
+
+#[test]
+fn rejects_jwts_declaring_none() {
+    let jwt = to_jwt(&claims(), SECRET).unwrap();
+    let (_, rest) = jwt.split_once('.').unwrap();
+    let header = URL_SAFE_NO_PAD.encode(r#"{"alg":"none","typ":"JWT"}"#);
+    let forged = format!("{}.{}", header, rest);
+
+    let err = from_jwt::<Claims>(SECRET, &forged).unwrap_err();
+    assert_eq!(*err.kind(), TokenErrorKind::UnsupportedAlgorithm("none".to_string()));
+    assert!(from_jwt::<Claims>(SECRET, &jwt).is_ok());
+}
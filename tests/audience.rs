@@ -0,0 +1,65 @@
+#![cfg(feature = "msgpack")]
+
+use crabtoken::{create_token, verify, Audience, Claims, TokenErrorKind, Validation};
+
+mod common;
+
+use common::{claims, SECRET};
+
+fn audiences(auds: &[&str]) -> Audience {
+    Audience::Multiple(auds.iter().map(|aud| aud.to_string()).collect())
+}
+
+// Verifies a token with the given `aud` claim against `expected_aud`.
+fn accepts(aud: Option<Audience>, expected: Audience) -> bool {
+    let mut claims = claims();
+    claims.aud = aud;
+    let token = create_token(&claims, SECRET).unwrap();
+    let validation = Validation {
+        expected_aud: Some(expected),
+        ..Validation::default()
+    };
+    match verify::<Claims>(SECRET, &token, &validation) {
+        Ok(_) => true,
+        Err(err) => {
+            assert_eq!(*err.kind(), TokenErrorKind::InvalidAudience);
+            false
+        }
+    }
+}
+
+#[test]
+fn single_expected_audience() {
+    let expected = || Audience::from("api");
+    assert!(accepts(Some("api".into()), expected()));
+    assert!(!accepts(Some("web".into()), expected()));
+    assert!(accepts(Some(audiences(&["web", "api"])), expected()));
+    assert!(!accepts(Some(audiences(&["web", "admin"])), expected()));
+    assert!(!accepts(None, expected()));
+}
+
+#[test]
+fn several_expected_audiences_intersect_the_token_audiences() {
+    let expected = || audiences(&["api", "billing"]);
+    assert!(accepts(Some("api".into()), expected()));
+    assert!(accepts(Some("billing".into()), expected()));
+    assert!(!accepts(Some("web".into()), expected()));
+    assert!(accepts(Some(audiences(&["web", "api"])), expected()));
+    assert!(accepts(Some(audiences(&["billing", "api"])), expected()));
+    assert!(!accepts(Some(audiences(&["web", "admin"])), expected()));
+    assert!(!accepts(None, expected()));
+}
+
+#[test]
+fn single_element_list_is_the_same_as_a_single_audience() {
+    assert!(accepts(Some("api".into()), audiences(&["api"])));
+    assert!(accepts(Some(audiences(&["api"])), "api".into()));
+    assert!(!accepts(Some(audiences(&["web"])), "api".into()));
+}
+
+#[test]
+fn empty_expected_list_accepts_no_token() {
+    assert!(!accepts(Some("api".into()), audiences(&[])));
+    assert!(!accepts(Some(audiences(&["web", "api"])), audiences(&[])));
+    assert!(!accepts(None, audiences(&[])));
+}
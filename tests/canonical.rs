@@ -0,0 +1,59 @@
+#![cfg(feature = "msgpack")]
+
+use crabtoken::{create_token_with_options, Format, TokenOptions};
+use serde::{Serialize, Serializer};
+use serde_json::{json, Value};
+
+mod common;
+
+use common::SECRET;
+
+// A map serialized in the order its entries were inserted.
+struct Ordered(Vec<(&'static str, Value)>);
+
+impl Serialize for Ordered {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.0.iter().map(|(key, value)| (key, value)))
+    }
+}
+
+fn forward() -> Ordered {
+    Ordered(vec![
+        ("sub", json!("alice")),
+        ("exp", json!(1_700_000_000)),
+        ("roles", json!(["admin", "billing"])),
+        ("org", json!({ "id": 7, "name": "tacos" })),
+    ])
+}
+
+fn reversed() -> Ordered {
+    let mut entries = forward().0;
+    entries.reverse();
+    Ordered(entries)
+}
+
+fn token(payload: &Ordered, format: Format, canonical: bool) -> String {
+    let options = TokenOptions {
+        format,
+        canonical,
+        ..TokenOptions::default()
+    };
+    create_token_with_options(payload, SECRET, &options).unwrap().into_string()
+}
+
+#[test]
+fn insertion_order_changes_non_canonical_tokens() {
+    for format in [Format::MessagePack, Format::Json] {
+        assert_ne!(token(&forward(), format, false), token(&reversed(), format, false));
+    }
+}
+
+#[test]
+fn canonical_tokens_ignore_insertion_order() {
+    for format in [Format::MessagePack, Format::Json] {
+        let canonical = token(&forward(), format, true);
+        assert_eq!(canonical, token(&reversed(), format, true), "{:?}", format);
+        // Serializing again gives the same bytes, and so the same token.
+        assert_eq!(canonical, token(&forward(), format, true));
+    }
+}
@@ -0,0 +1,19 @@
+// Fixtures shared by the integration tests. Each test file uses only some
+// of them.
+#![allow(dead_code)]
+
+use crabtoken::{create_token, Claims};
+
+// A secret long enough to pass the `MIN_SECRET_LEN` check.
+pub const SECRET: &str = "Just a cat eating tacos on a sunny afternoon";
+
+// Claims for `alice` expiring so far in the future that they never expire
+// during a test.
+pub fn claims() -> Claims {
+    Claims::builder().subject("alice").expires_at(i64::MAX / 1000).build()
+}
+
+// A token for `claims`, signed with `SECRET`.
+pub fn token() -> String {
+    create_token(&claims(), SECRET).unwrap().into_string()
+}
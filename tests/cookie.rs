@@ -0,0 +1,72 @@
+#![cfg(feature = "msgpack")]
+
+use crabtoken::{
+    from_cookie_chunks, to_cookie_chunks, verify_token, Claims, TokenError, TokenErrorKind,
+};
+
+mod common;
+
+use common::{claims, token, SECRET};
+
+const CHUNK_LEN: usize = 16;
+
+fn chunks(token: &str) -> Vec<(String, String)> {
+    let chunks = to_cookie_chunks("tok", token, CHUNK_LEN).unwrap();
+    assert!(chunks.len() > 3, "the test token needs several chunks");
+    chunks
+}
+
+fn reassemble(chunks: &[(String, String)]) -> Result<String, TokenError> {
+    let cookies = chunks.iter().map(|(name, value)| (name.as_str(), value.as_str()));
+    from_cookie_chunks("tok", cookies).map(|token| token.into_string())
+}
+
+fn named(name: &str, value: &str) -> (String, String) {
+    (name.to_string(), value.to_string())
+}
+
+#[test]
+fn chunks_round_trip_in_any_order() {
+    let token = token();
+    let mut chunks = chunks(&token);
+    assert_eq!(chunks[0].0, "tok.0");
+    assert!(chunks.iter().all(|(_, value)| value.len() <= CHUNK_LEN));
+
+    chunks.reverse();
+    chunks.push(named("session", "unrelated"));
+    chunks.push(named("tok.sig", "unrelated"));
+    let reassembled = reassemble(&chunks).unwrap();
+    assert_eq!(reassembled, token);
+    assert_eq!(verify_token::<Claims>(SECRET, &reassembled).unwrap(), claims());
+}
+
+#[test]
+fn rejects_a_gap_in_the_chunks() {
+    let mut chunks = chunks(&token());
+    chunks.remove(1);
+    assert_eq!(*reassemble(&chunks).unwrap_err().kind(), TokenErrorKind::InvalidFormat);
+}
+
+#[test]
+fn a_missing_last_chunk_fails_verification() {
+    let mut chunks = chunks(&token());
+    chunks.pop();
+    let verified = reassemble(&chunks).and_then(|token| verify_token::<Claims>(SECRET, &token));
+    assert!(verified.is_err());
+}
+
+#[test]
+fn rejects_repeated_and_non_canonical_indices() {
+    let token = token();
+    for stray in ["tok.00", "tok.01", "tok.1"] {
+        let mut chunks = chunks(&token);
+        chunks.push(named(stray, "forged"));
+        let err = reassemble(&chunks).unwrap_err();
+        assert_eq!(*err.kind(), TokenErrorKind::InvalidFormat, "{}", stray);
+    }
+
+    // Not an index at all, so not one of the chunks.
+    let mut chunks = chunks(&token);
+    chunks.push(named("tok.+0", "forged"));
+    assert_eq!(reassemble(&chunks).unwrap(), token);
+}
@@ -0,0 +1,71 @@
+#![cfg(feature = "msgpack")]
+
+use crabtoken::{
+    create_token_with_options, verify, Expirable, Format, TokenErrorKind, TokenOptions,
+    Validation,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+mod common;
+
+use common::SECRET;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Nested {
+    exp: i64,
+    data: Value,
+}
+
+impl Expirable for Nested {
+    fn exp(&self) -> i64 {
+        self.exp
+    }
+}
+
+// Verifies a payload holding `data`, allowing 3 levels of nesting: the
+// payload itself and two more.
+fn verify_nested(format: Format, data: Value) -> Result<(), TokenErrorKind> {
+    let payload = Nested {
+        exp: i64::MAX / 1000,
+        data,
+    };
+    let options = TokenOptions {
+        format,
+        ..TokenOptions::default()
+    };
+    let token = create_token_with_options(&payload, SECRET, &options).unwrap();
+    let validation = Validation {
+        max_depth: 3,
+        ..Validation::default()
+    };
+    verify::<Nested>(SECRET, &token, &validation)
+        .map(|_| ())
+        .map_err(|err| err.kind().clone())
+}
+
+#[test]
+fn accepts_payloads_nested_up_to_max_depth() {
+    for format in [Format::Json, Format::MessagePack] {
+        assert_eq!(verify_nested(format, json!([[1]])), Ok(()));
+        assert_eq!(verify_nested(format, json!({ "a": { "b": 1 } })), Ok(()));
+    }
+}
+
+#[test]
+fn rejects_payloads_nested_one_level_deeper() {
+    for format in [Format::Json, Format::MessagePack] {
+        assert_eq!(verify_nested(format, json!([[[1]]])), Err(TokenErrorKind::TooDeep));
+        assert_eq!(verify_nested(format, json!([{ "b": [1] }])), Err(TokenErrorKind::TooDeep));
+    }
+}
+
+#[test]
+fn brackets_inside_json_strings_do_not_count() {
+    assert_eq!(verify_nested(Format::Json, json!([["[[[{{{"]])), Ok(()));
+    // An escaped quote doesn't end the string, so the brackets after it
+    // are still inside it.
+    assert_eq!(verify_nested(Format::Json, json!([["\"[[[", "\\"]])), Ok(()));
+    // Closing brackets in strings don't lower the depth either.
+    assert_eq!(verify_nested(Format::Json, json!(["]]]", [[1]]])), Err(TokenErrorKind::TooDeep));
+}
@@ -0,0 +1,92 @@
+#![cfg(feature = "msgpack")]
+
+use base64::engine::general_purpose::URL_SAFE;
+use base64::Engine;
+use crabtoken::{decode_token, verify_token, Claims, Sha256, TokenErrorKind};
+use hmac::{Hmac, Mac};
+
+mod common;
+
+use common::{token, SECRET};
+
+const SEGMENTS: [&str; 3] = ["header", "payload", "signature"];
+
+// Replaces the second character of the given segment, keeping its length.
+fn with_char(token: &str, segment: usize, ch: char) -> String {
+    let mut segments: Vec<String> = token.split('.').map(String::from).collect();
+    let mut chars: Vec<char> = segments[segment].chars().collect();
+    chars[1] = ch;
+    segments[segment] = chars.into_iter().collect();
+    segments.join(".")
+}
+
+fn assert_invalid_encoding(ch: char) {
+    for (index, name) in SEGMENTS.iter().enumerate() {
+        let token = with_char(&token(), index, ch);
+        let err = verify_token::<Claims>(SECRET, &token).unwrap_err();
+        assert_eq!(
+            *err.kind(),
+            TokenErrorKind::InvalidEncoding(name),
+            "{:?} in the {}",
+            ch,
+            name
+        );
+        assert!(err.to_string().contains(name));
+    }
+}
+
+#[test]
+fn rejects_plus_in_each_segment() {
+    assert_invalid_encoding('+');
+}
+
+#[test]
+fn rejects_slash_in_each_segment() {
+    assert_invalid_encoding('/');
+}
+
+#[test]
+fn rejects_misplaced_padding_in_each_segment() {
+    assert_invalid_encoding('=');
+}
+
+#[test]
+fn rejects_whitespace_in_each_segment() {
+    assert_invalid_encoding(' ');
+    assert_invalid_encoding('\n');
+}
+
+// Pads a segment with `=` to a multiple of four characters.
+fn pad(segment: &str) -> String {
+    format!("{}{}", segment, "=".repeat((4 - segment.len() % 4) % 4))
+}
+
+// Re-encodes the token as JWT tooling that pads base64url would: every
+// segment padded, and the signature computed over the padded segments.
+fn padded(token: &str) -> String {
+    let mut segments = token.split('.');
+    let header = pad(segments.next().unwrap());
+    let payload = pad(segments.next().unwrap());
+    let signing_input = format!("{}.{}", header, payload);
+    let mut mac = Hmac::<Sha256>::new_from_slice(SECRET.as_bytes()).unwrap();
+    mac.update(signing_input.as_bytes());
+    let signature = URL_SAFE.encode(mac.finalize().into_bytes());
+    format!("{}.{}", signing_input, signature)
+}
+
+#[test]
+fn creates_unpadded_tokens() {
+    assert!(!token().contains('='));
+}
+
+#[test]
+fn accepts_padded_and_unpadded_segments() {
+    let token = token();
+    let padded = padded(&token);
+    assert!(padded.contains('='), "the test token needs a segment to pad");
+
+    let unpadded_claims = verify_token::<Claims>(SECRET, &token).unwrap();
+    let padded_claims = verify_token::<Claims>(SECRET, &padded).unwrap();
+    assert_eq!(padded_claims, unpadded_claims);
+    assert_eq!(decode_token::<Claims>(&padded).unwrap(), unpadded_claims);
+}
@@ -0,0 +1,97 @@
+#![cfg(feature = "msgpack")]
+
+use crabtoken::{
+    create_token, verify_token_no_exp, verify_with_clock, Claims, Clock, Expirable, FixedClock,
+    TokenErrorKind, Validation,
+};
+use serde::{Deserialize, Serialize};
+
+mod common;
+
+use common::SECRET;
+
+const NOW: i64 = 1_700_000_000;
+
+fn verify_at(exp: i64, now: i64) -> Result<Claims, TokenErrorKind> {
+    let token = create_token(&Claims::builder().expires_at(exp).build(), SECRET).unwrap();
+    verify_with_clock(SECRET, &token, &Validation::default(), &FixedClock(now))
+        .map_err(|err| err.kind().clone())
+}
+
+#[test]
+fn token_is_valid_until_the_second_before_exp() {
+    assert!(verify_at(NOW + 1, NOW).is_ok());
+}
+
+#[test]
+fn token_expires_when_now_reaches_exp() {
+    assert_eq!(verify_at(NOW, NOW).unwrap_err(), TokenErrorKind::Expired);
+}
+
+#[test]
+fn token_stays_expired_after_exp() {
+    assert_eq!(verify_at(NOW - 1, NOW).unwrap_err(), TokenErrorKind::Expired);
+}
+
+#[derive(Serialize, Deserialize)]
+struct ShortLived {
+    exp_ms: i64,
+}
+
+impl Expirable for ShortLived {
+    fn exp(&self) -> i64 {
+        self.exp_ms.div_euclid(1000)
+    }
+
+    fn exp_millis(&self) -> Option<i64> {
+        Some(self.exp_ms)
+    }
+}
+
+struct MillisClock(i64);
+
+impl Clock for MillisClock {
+    fn now(&self) -> i64 {
+        self.0.div_euclid(1000)
+    }
+
+    fn now_millis(&self) -> i64 {
+        self.0
+    }
+}
+
+#[test]
+fn millisecond_expiry_has_the_same_boundary() {
+    let now_ms = NOW * 1000 + 250;
+    let verify_at = |exp_ms: i64| {
+        let token = create_token(&ShortLived { exp_ms }, SECRET).unwrap();
+        let clock = MillisClock(now_ms);
+        verify_with_clock::<ShortLived>(SECRET, &token, &Validation::default(), &clock)
+            .map(|_| ())
+            .map_err(|err| err.kind().clone())
+    };
+
+    assert_eq!(verify_at(now_ms + 1), Ok(()));
+    assert_eq!(verify_at(now_ms), Err(TokenErrorKind::Expired));
+    assert_eq!(verify_at(now_ms - 1), Err(TokenErrorKind::Expired));
+}
+
+// A service credential that is revoked by hand rather than expiring.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct ApiKey {
+    key_id: String,
+}
+
+#[test]
+fn tokens_without_exp_verify_with_verify_token_no_exp() {
+    let key = ApiKey {
+        key_id: "svc-billing".into(),
+    };
+    let token = create_token(&key, SECRET).unwrap();
+    assert_eq!(verify_token_no_exp::<ApiKey>(SECRET, &token).unwrap(), key);
+
+    let (signing_input, _) = token.rsplit_once('.').unwrap();
+    let forged = format!("{}.{}", signing_input, "A".repeat(43));
+    let err = verify_token_no_exp::<ApiKey>(SECRET, &forged).unwrap_err();
+    assert_eq!(*err.kind(), TokenErrorKind::InvalidSignature);
+}
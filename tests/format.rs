@@ -0,0 +1,83 @@
+#![cfg(feature = "msgpack")]
+
+use crabtoken::{verify, verify_token, Claims, Token, TokenErrorKind, Validation};
+
+mod common;
+
+use common::{token, SECRET};
+
+fn assert_invalid_format(token: &str) {
+    let err = token.parse::<Token>().unwrap_err();
+    assert_eq!(*err.kind(), TokenErrorKind::InvalidFormat, "parsing {:?}", token);
+    let err = verify_token::<Claims>(SECRET, token).unwrap_err();
+    assert_eq!(*err.kind(), TokenErrorKind::InvalidFormat, "verifying {:?}", token);
+    let json = serde_json::to_string(token).unwrap();
+    assert!(serde_json::from_str::<Token>(&json).is_err(), "deserializing {:?}", token);
+}
+
+fn legacy_validation() -> Validation {
+    Validation {
+        allow_legacy: true,
+        ..Validation::default()
+    }
+}
+
+#[test]
+fn rejects_a_single_segment() {
+    assert_invalid_format("a");
+    assert_invalid_format("");
+}
+
+#[test]
+fn rejects_two_segments_unless_legacy_tokens_are_allowed() {
+    assert_invalid_format("a.b");
+}
+
+#[test]
+fn accepts_the_shape_of_three_segments() {
+    // "a.b.c" is shaped like a token; it only fails once decoded.
+    assert!("a.b.c".parse::<Token>().is_ok());
+    let err = verify_token::<Claims>(SECRET, "a.b.c").unwrap_err();
+    assert_ne!(*err.kind(), TokenErrorKind::InvalidFormat);
+}
+
+#[test]
+fn rejects_extra_dots() {
+    assert_invalid_format("a.b.c.d");
+    assert_invalid_format(&format!("{}.", token()));
+    assert_invalid_format(&format!("{}.extra", token()));
+}
+
+#[test]
+fn rejects_empty_segments() {
+    assert_invalid_format("..");
+    assert_invalid_format(".b.c");
+    assert_invalid_format("a..c");
+    assert_invalid_format("a.b.");
+}
+
+#[test]
+fn rejects_leading_trailing_and_lone_dots() {
+    let token = token();
+    assert_invalid_format(&format!(".{}", token));
+    assert_invalid_format(&format!("{}.", token));
+    assert_invalid_format(".");
+
+    // The same holds for tokens in the original two-segment format.
+    for token in [".abc", "abc.", "."] {
+        let err = verify::<Claims>(SECRET, token, &legacy_validation()).unwrap_err();
+        assert_eq!(*err.kind(), TokenErrorKind::InvalidFormat, "{:?}", token);
+    }
+}
+
+#[test]
+fn deserializes_tokens_as_checked_strings() {
+    let token = token();
+    let json = serde_json::to_string(&token).unwrap();
+    let parsed: Token = serde_json::from_str(&json).unwrap();
+    assert_eq!(parsed.as_str(), token);
+    assert_eq!(serde_json::to_string(&parsed).unwrap(), json);
+
+    let err = serde_json::from_str::<Token>(r#""not a token""#).unwrap_err();
+    assert!(err.to_string().contains(&TokenErrorKind::InvalidFormat.to_string()));
+}
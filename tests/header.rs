@@ -0,0 +1,102 @@
+#![cfg(feature = "msgpack")]
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use crabtoken::{
+    create_token_with_options, decode_header, Algorithm, Format, Header, TokenErrorKind,
+    TokenOptions,
+};
+use serde_json::json;
+
+mod common;
+
+use common::{claims, token, SECRET};
+
+fn header_bytes(token: &str) -> Vec<u8> {
+    let (header, _) = token.split_once('.').unwrap();
+    URL_SAFE_NO_PAD.decode(header).unwrap()
+}
+
+// Replaces the token's header with `header`, encoded by rmp_serde.
+fn with_header(token: &str, header: serde_json::Value) -> String {
+    let header = URL_SAFE_NO_PAD.encode(rmp_serde::to_vec_named(&header).unwrap());
+    let (_, rest) = token.split_once('.').unwrap();
+    format!("{}.{}", header, rest)
+}
+
+// Headers are written without rmp_serde, but must stay byte for byte what
+// it writes, so tokens created before keep the same signatures.
+#[test]
+fn writes_headers_as_rmp_serde_does() {
+    let algs = [Algorithm::Hs256, Algorithm::Hs384, Algorithm::Hs512];
+    let formats = [Format::MessagePack, Format::Json];
+    let ids = [None, Some("k".repeat(40))];
+    for alg in algs {
+        for format in formats {
+            for id in &ids {
+                let options = TokenOptions {
+                    alg,
+                    format,
+                    kid: id.clone(),
+                    typ: id.clone(),
+                    ..TokenOptions::default()
+                };
+                let token = create_token_with_options(&claims(), SECRET, &options).unwrap();
+                let header = Header {
+                    ver: 1,
+                    alg,
+                    fmt: format,
+                    kid: id.clone(),
+                    typ: id.clone(),
+                };
+                assert_eq!(header_bytes(&token), rmp_serde::to_vec_named(&header).unwrap());
+                assert_eq!(decode_header(&token).unwrap(), header);
+            }
+        }
+    }
+}
+
+#[test]
+fn reads_headers_like_a_derived_deserialize() {
+    let token = token();
+    let header = with_header(
+        &token,
+        json!({ "alg": "HS256", "kid": null, "extra": { "nested": [1, -2, 3.5, "x"] } }),
+    );
+    let decoded = decode_header(&header).unwrap();
+    assert_eq!(decoded.ver, 1);
+    assert_eq!(decoded.fmt, Format::MessagePack);
+    assert_eq!(decoded.kid, None);
+}
+
+#[test]
+fn rejects_malformed_headers() {
+    let token = token();
+    let invalid = [
+        json!({ "ver": 1, "fmt": "msgpack" }),
+        json!({ "ver": 1, "alg": 256, "fmt": "msgpack" }),
+        json!({ "ver": -1, "alg": "HS256" }),
+        json!({ "ver": 300, "alg": "HS256" }),
+        json!({ "alg": "HS256", "fmt": "yaml" }),
+        json!({ "alg": "HS256", "kid": 7 }),
+        json!(["HS256"]),
+        json!("HS256"),
+    ];
+    for header in invalid {
+        let err = decode_header(&with_header(&token, header.clone())).unwrap_err();
+        assert!(matches!(err.kind(), TokenErrorKind::Decode(_)), "{}: {:?}", header, err);
+    }
+
+    // A field given twice, which a JSON map can't hold.
+    let mut duplicate = vec![0x82];
+    for _ in 0..2 {
+        duplicate.extend_from_slice(b"\xa3alg\xa5HS256");
+    }
+    let (_, rest) = token.split_once('.').unwrap();
+    let header = format!("{}.{}", URL_SAFE_NO_PAD.encode(duplicate), rest);
+    assert!(matches!(decode_header(&header).unwrap_err().kind(), TokenErrorKind::Decode(_)));
+
+    let header = with_header(&token, json!({ "ver": 2, "alg": "HS256" }));
+    let err = decode_header(&header).unwrap_err();
+    assert_eq!(*err.kind(), TokenErrorKind::UnsupportedVersion(2));
+}
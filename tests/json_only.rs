@@ -0,0 +1,29 @@
+#![cfg(all(feature = "std", not(feature = "msgpack")))]
+
+// Without the `msgpack` feature, as on `no_std` targets, tokens carry JSON
+// payloads. Run with `cargo test --no-default-features --features std`.
+
+use crabtoken::{
+    create_token, create_token_with_options, verify_token, Claims, Format, TokenErrorKind,
+    TokenOptions,
+};
+
+mod common;
+
+use common::{claims, SECRET};
+
+#[test]
+fn json_tokens_round_trip() {
+    let options = TokenOptions {
+        format: Format::Json,
+        ..TokenOptions::default()
+    };
+    let token = create_token_with_options(&claims(), SECRET, &options).unwrap();
+    assert_eq!(verify_token::<Claims>(SECRET, &token).unwrap(), claims());
+}
+
+#[test]
+fn messagepack_payloads_need_the_feature() {
+    let err = create_token(&claims(), SECRET).unwrap_err();
+    assert!(matches!(err.kind(), TokenErrorKind::Serialize(_)));
+}
@@ -0,0 +1,61 @@
+#![cfg(feature = "msgpack")]
+
+use crabtoken::{
+    create_token, verify_token, Claims, SecretKey, Signer, TokenErrorKind, TokenOptions,
+    Validation, Verifier, MIN_SECRET_LEN,
+};
+
+mod common;
+
+use common::claims;
+
+const SHORT: &str = "tacos";
+
+#[test]
+fn rejects_short_secrets() {
+    let short = "x".repeat(MIN_SECRET_LEN - 1);
+    for secret in ["", SHORT, short.as_str()] {
+        let err = create_token(&claims(), secret).unwrap_err();
+        assert_eq!(*err.kind(), TokenErrorKind::WeakKey);
+        assert_eq!(*Signer::new(secret).unwrap_err().kind(), TokenErrorKind::WeakKey);
+        assert_eq!(*Verifier::new(secret).unwrap_err().kind(), TokenErrorKind::WeakKey);
+    }
+    assert!(create_token(&claims(), "x".repeat(MIN_SECRET_LEN)).is_ok());
+}
+
+#[test]
+fn unchecked_keys_accept_short_secrets() {
+    let signer = Signer::new_unchecked(SHORT, TokenOptions::default()).unwrap();
+    let verifier = Verifier::new_unchecked(SHORT, Validation::default()).unwrap();
+    let token = signer.sign(&claims()).unwrap();
+    assert_eq!(verifier.verify::<Claims>(&token).unwrap(), claims());
+
+    // Opting out for one key doesn't weaken the checked functions.
+    let err = verify_token::<Claims>(SHORT, &token).unwrap_err();
+    assert_eq!(*err.kind(), TokenErrorKind::WeakKey);
+}
+
+fn hex(hex: &str) -> Vec<u8> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+        .collect()
+}
+
+// RFC 5869, test case 1. `derive` returns the first 32 bytes of the OKM.
+#[test]
+fn derive_matches_rfc_5869_test_case_1() {
+    let ikm = [0x0b; 22];
+    let salt = hex("000102030405060708090a0b0c");
+    let info = hex("f0f1f2f3f4f5f6f7f8f9");
+    let okm = hex("3cb25f25faacd57a90434f64d0362f2a2d2d0a90cf1a5a4c5db02d56ecc4c5bf");
+    assert_eq!(SecretKey::derive(ikm, salt, info).unwrap().as_bytes(), okm);
+}
+
+// RFC 5869, test case 3: no salt and no info.
+#[test]
+fn derive_matches_rfc_5869_test_case_3() {
+    let ikm = [0x0b; 22];
+    let okm = hex("8da4e775a563c18f715f802a063c5a31b8a11f5c5ee1879ec3454e5f3c738d2d");
+    assert_eq!(SecretKey::derive(ikm, [], []).unwrap().as_bytes(), okm);
+}
@@ -0,0 +1,63 @@
+#![cfg(feature = "msgpack")]
+
+use crabtoken::{
+    create_token_with_options, verify_by_kid_with, verify_token_by_kid, Algorithm, Claims,
+    SecretKey, TokenErrorKind, TokenOptions, Validation,
+};
+
+mod common;
+
+use common::{claims, SECRET};
+
+fn lookup(kid: &str) -> Option<SecretKey> {
+    (kid == "current").then(|| SecretKey::from(SECRET))
+}
+
+fn token_with(kid: &str, alg: Algorithm, claims: &Claims) -> String {
+    let options = TokenOptions {
+        alg,
+        kid: Some(kid.to_string()),
+        ..TokenOptions::default()
+    };
+    create_token_with_options(claims, SECRET, &options).unwrap().into_string()
+}
+
+#[test]
+fn looks_up_the_secret_by_key_id() {
+    let token = token_with("current", Algorithm::Hs256, &claims());
+    assert_eq!(verify_token_by_kid::<Claims, _>(lookup, &token).unwrap(), claims());
+
+    let unknown = token_with("retired", Algorithm::Hs256, &claims());
+    let err = verify_token_by_kid::<Claims, _>(lookup, &unknown).unwrap_err();
+    assert_eq!(*err.kind(), TokenErrorKind::UnknownKeyId);
+
+    let err = verify_token_by_kid::<Claims, _>(lookup, &common::token()).unwrap_err();
+    assert_eq!(*err.kind(), TokenErrorKind::MissingKeyId);
+}
+
+#[test]
+fn applies_the_validation() {
+    let validation = Validation {
+        algorithms: vec![Algorithm::Hs512],
+        ..Validation::default()
+    };
+    let hs256 = token_with("current", Algorithm::Hs256, &claims());
+    let err = verify_by_kid_with::<Claims, _>(lookup, &hs256, &validation).unwrap_err();
+    assert_eq!(*err.kind(), TokenErrorKind::AlgorithmMismatch);
+    let hs512 = token_with("current", Algorithm::Hs512, &claims());
+    assert!(verify_by_kid_with::<Claims, _>(lookup, &hs512, &validation).is_ok());
+
+    let validation = Validation {
+        expected_iss: Some("issuer".into()),
+        ..Validation::default()
+    };
+    let err = verify_by_kid_with::<Claims, _>(lookup, &hs256, &validation).unwrap_err();
+    assert_eq!(*err.kind(), TokenErrorKind::InvalidIssuer);
+
+    let validation = Validation {
+        max_token_len: 16,
+        ..Validation::default()
+    };
+    let err = verify_by_kid_with::<Claims, _>(lookup, &hs256, &validation).unwrap_err();
+    assert_eq!(*err.kind(), TokenErrorKind::TooLarge);
+}
@@ -0,0 +1,63 @@
+#![cfg(all(feature = "msgpack", feature = "log"))]
+
+// Run with `cargo test --features log`.
+
+use crabtoken::{verify, verify_token, Claims, Validation};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::sync::Mutex;
+
+mod common;
+
+use common::{token, SECRET};
+
+// Keeps the messages logged by the crate.
+struct Captured(Mutex<Vec<String>>);
+
+impl Log for Captured {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.target() == "crabtoken"
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        if self.enabled(record.metadata()) {
+            assert_eq!(record.level(), Level::Debug);
+            self.0.lock().unwrap().push(record.args().to_string());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: Captured = Captured(Mutex::new(Vec::new()));
+
+fn logged(verify: impl FnOnce()) -> String {
+    LOGGER.0.lock().unwrap().clear();
+    verify();
+    LOGGER.0.lock().unwrap().join("\n")
+}
+
+// One test, since the logger is global to the test binary.
+#[test]
+fn logs_why_tokens_are_rejected() {
+    log::set_logger(&LOGGER).unwrap();
+    log::set_max_level(LevelFilter::Debug);
+    let token = token();
+
+    let message = logged(|| assert!(verify_token::<Claims>(SECRET, "a.b").is_err()));
+    assert_eq!(message, "token rejected at parse: Invalid token format");
+
+    let forged = format!("{}A", token);
+    let message = logged(|| assert!(verify_token::<Claims>(SECRET, &forged).is_err()));
+    assert!(message.starts_with("token rejected at signature (alg HS256, kid None)"), "{}", message);
+
+    let validation = Validation {
+        expected_iss: Some("issuer".into()),
+        ..Validation::default()
+    };
+    let message = logged(|| assert!(verify::<Claims>(SECRET, &token, &validation).is_err()));
+    assert!(message.starts_with("token rejected at claims"), "{}", message);
+
+    let message = logged(|| assert!(verify_token::<Claims>(SECRET, &token).is_ok()));
+    assert_eq!(message, "");
+    assert!(!message.contains(SECRET));
+}
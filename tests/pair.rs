@@ -0,0 +1,87 @@
+#![cfg(feature = "msgpack")]
+
+use crabtoken::{
+    create_pair_with, rotate_with, Claims, FixedClock, IdSource, NonceStore, SeededIds,
+    TokenErrorKind,
+};
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::time::Duration;
+
+mod common;
+
+use common::SECRET;
+
+const NOW: i64 = 1_700_000_000;
+
+const ACCESS_TTL: Duration = Duration::from_secs(60);
+
+const REFRESH_TTL: Duration = Duration::from_secs(3600);
+
+// Hands out seeded ids, recording whether any refresh token had been spent
+// by the time the new one was minted.
+struct WatchedIds<'a> {
+    ids: SeededIds,
+    used: &'a RefCell<HashSet<String>>,
+    spent_before_mint: bool,
+}
+
+// A store shared with `WatchedIds`.
+struct SharedStore<'a>(&'a RefCell<HashSet<String>>);
+
+impl NonceStore for SharedStore<'_> {
+    fn check_and_insert(&mut self, jti: &str) -> bool {
+        self.0.borrow_mut().insert(jti.to_string())
+    }
+}
+
+impl IdSource for WatchedIds<'_> {
+    fn next_id(&mut self) -> String {
+        self.spent_before_mint |= !self.used.borrow().is_empty();
+        self.ids.next_id()
+    }
+}
+
+fn refresh_token() -> String {
+    let claims = Claims::builder().subject("alice").build();
+    let clock = FixedClock(NOW);
+    let pair =
+        create_pair_with(&claims, SECRET, ACCESS_TTL, REFRESH_TTL, &clock, &mut SeededIds::new(1))
+            .unwrap();
+    pair.refresh_token.into_string()
+}
+
+#[test]
+fn mints_the_new_pair_before_spending_the_refresh_token() {
+    let token = refresh_token();
+    let used = RefCell::new(HashSet::new());
+    let mut ids = WatchedIds {
+        ids: SeededIds::new(2),
+        used: &used,
+        spent_before_mint: false,
+    };
+    let clock = FixedClock(NOW);
+
+    let mut store = SharedStore(&used);
+    rotate_with(&token, SECRET, ACCESS_TTL, REFRESH_TTL, &mut store, &clock, &mut ids).unwrap();
+    assert!(!ids.spent_before_mint);
+    assert_eq!(used.borrow().len(), 1);
+}
+
+#[test]
+fn rejects_a_refresh_token_used_twice() {
+    let token = refresh_token();
+    let clock = FixedClock(NOW);
+    let mut used = HashSet::new();
+    let mut ids = SeededIds::new(2);
+
+    let pair =
+        rotate_with(&token, SECRET, ACCESS_TTL, REFRESH_TTL, &mut used, &clock, &mut ids).unwrap();
+    let err = rotate_with(&token, SECRET, ACCESS_TTL, REFRESH_TTL, &mut used, &clock, &mut ids)
+        .unwrap_err();
+    assert_eq!(*err.kind(), TokenErrorKind::AlreadyUsed);
+
+    let refresh = pair.refresh_token;
+    assert!(rotate_with(&refresh, SECRET, ACCESS_TTL, REFRESH_TTL, &mut used, &clock, &mut ids)
+        .is_ok());
+}
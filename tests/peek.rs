@@ -0,0 +1,51 @@
+#![cfg(feature = "msgpack")]
+
+use crabtoken::{create_token_with_options, peek_exp, Format, TokenErrorKind, TokenOptions};
+use serde::Serialize;
+
+mod common;
+
+use common::{claims, token, SECRET};
+
+#[test]
+fn reads_the_exp_claim() {
+    assert_eq!(peek_exp(&token()).unwrap(), claims().exp);
+
+    let options = TokenOptions {
+        format: Format::Json,
+        ..TokenOptions::default()
+    };
+    let token = create_token_with_options(&claims(), SECRET, &options).unwrap();
+    assert_eq!(peek_exp(&token).unwrap(), claims().exp);
+}
+
+// A struct whose first field is not `exp`.
+#[derive(Serialize)]
+struct Positional {
+    uid: i64,
+    exp: i64,
+}
+
+#[test]
+fn rejects_payloads_that_are_not_maps() {
+    let payload = Positional { uid: 42, exp: 1_700_000_000 };
+    let options = TokenOptions {
+        named_fields: false,
+        ..TokenOptions::default()
+    };
+    let token = create_token_with_options(&payload, SECRET, &options).unwrap();
+    assert!(matches!(
+        peek_exp(&token).unwrap_err().kind(),
+        TokenErrorKind::PayloadTypeMismatch { found: "an array", .. }
+    ));
+
+    let options = TokenOptions {
+        format: Format::Json,
+        ..TokenOptions::default()
+    };
+    let token = create_token_with_options(&(42, 1_700_000_000), SECRET, &options).unwrap();
+    assert!(matches!(
+        peek_exp(&token).unwrap_err().kind(),
+        TokenErrorKind::PayloadTypeMismatch { found: "an array", .. }
+    ));
+}
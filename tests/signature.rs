@@ -0,0 +1,42 @@
+#![cfg(feature = "msgpack")]
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use crabtoken::{constant_time_eq, verify_token, Claims, TokenErrorKind};
+
+mod common;
+
+use common::{token, SECRET};
+
+// Flips the bits of one byte of the token's signature.
+fn with_flipped_signature_byte(token: &str, index: usize) -> String {
+    let (signing_input, signature) = token.rsplit_once('.').unwrap();
+    let mut signature = URL_SAFE_NO_PAD.decode(signature).unwrap();
+    signature[index] ^= 0xff;
+    format!("{}.{}", signing_input, URL_SAFE_NO_PAD.encode(signature))
+}
+
+// The signature is compared in constant time, so a forgery matching all
+// but the last byte is rejected exactly like one wrong from the first.
+// Timing itself isn't measured here; both must fail the same way.
+#[test]
+fn rejects_signatures_differing_in_first_or_last_byte_alike() {
+    let token = token();
+    let last = URL_SAFE_NO_PAD.decode(token.rsplit_once('.').unwrap().1).unwrap().len() - 1;
+
+    for index in [0, last] {
+        let forged = with_flipped_signature_byte(&token, index);
+        let err = verify_token::<Claims>(SECRET, &forged).unwrap_err();
+        assert_eq!(*err.kind(), TokenErrorKind::InvalidSignature, "byte {}", index);
+    }
+    assert!(verify_token::<Claims>(SECRET, &token).is_ok());
+}
+
+#[test]
+fn constant_time_eq_compares_whole_slices() {
+    assert!(constant_time_eq(b"api-key-1234", b"api-key-1234"));
+    assert!(!constant_time_eq(b"api-key-1234", b"Xpi-key-1234"));
+    assert!(!constant_time_eq(b"api-key-1234", b"api-key-123X"));
+    assert!(!constant_time_eq(b"api-key-1234", b"api-key-123"));
+    assert!(constant_time_eq(b"", b""));
+}
@@ -0,0 +1,93 @@
+#![cfg(feature = "msgpack")]
+
+use crabtoken::{
+    create_token, verify_with_clock, Claims, ClaimRule, ClaimType, ClaimsSchema, Expirable,
+    FixedClock, TokenErrorKind, Validation,
+};
+use serde::Deserialize;
+
+mod common;
+
+use common::SECRET;
+
+const NOW: i64 = 1_700_000_000;
+
+// An expired token from the wrong issuer, without the `tenant` claim.
+fn token() -> String {
+    let claims = Claims::builder()
+        .issuer("someone-else")
+        .expires_at(NOW - 10)
+        .claim("scope", "read")
+        .claim("level", "high")
+        .build();
+    create_token(&claims, SECRET).unwrap().into_string()
+}
+
+fn validation(collect_errors: bool) -> Validation {
+    Validation {
+        expected_iss: Some("issuer".into()),
+        required_claims: vec!["tenant".into()],
+        required_scopes: vec!["write".into()],
+        schema: Some(ClaimsSchema::new().claim(ClaimRule::optional("level", ClaimType::Integer))),
+        collect_errors,
+        ..Validation::default()
+    }
+}
+
+fn verify<T>(validation: &Validation) -> TokenErrorKind
+where
+    T: for<'de> Deserialize<'de> + Expirable,
+{
+    let token = token();
+    match verify_with_clock::<T>(SECRET, &token, validation, &FixedClock(NOW)) {
+        Ok(_) => panic!("the token should be rejected"),
+        Err(err) => err.kind().clone(),
+    }
+}
+
+#[test]
+fn stops_at_the_first_failure_by_default() {
+    assert_eq!(
+        verify::<Claims>(&validation(false)),
+        TokenErrorKind::MissingClaim("tenant".into())
+    );
+}
+
+#[test]
+fn collects_payload_and_claim_failures_in_check_order() {
+    assert_eq!(
+        verify::<Claims>(&validation(true)),
+        TokenErrorKind::Multiple(vec![
+            TokenErrorKind::MissingClaim("tenant".into()),
+            TokenErrorKind::MissingScope("write".into()),
+            TokenErrorKind::InvalidClaim("level".into()),
+            TokenErrorKind::Expired,
+            TokenErrorKind::InvalidIssuer,
+        ])
+    );
+}
+
+#[derive(Deserialize)]
+struct Tenanted {
+    exp: i64,
+    #[allow(dead_code)]
+    tenant: String,
+}
+
+impl Expirable for Tenanted {
+    fn exp(&self) -> i64 {
+        self.exp
+    }
+}
+
+// A payload missing a required claim can't be decoded into a type that
+// needs it, so only the failures found on the decoded payload are known.
+#[test]
+fn reports_collected_failures_when_the_payload_does_not_fit_the_type() {
+    let validation = Validation {
+        required_scopes: Vec::new(),
+        schema: None,
+        ..validation(true)
+    };
+    assert_eq!(verify::<Tenanted>(&validation), TokenErrorKind::MissingClaim("tenant".into()));
+}
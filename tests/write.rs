@@ -0,0 +1,54 @@
+#![cfg(feature = "msgpack")]
+
+use crabtoken::{
+    create_token, create_token_with_options, write_token, write_token_with_options, Algorithm,
+    Encoding, Format, TokenOptions,
+};
+
+mod common;
+
+use common::{claims, SECRET};
+
+fn written(options: &TokenOptions) -> String {
+    let mut out = Vec::new();
+    write_token_with_options(&claims(), SECRET, options, &mut out).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn writes_the_same_token_as_create_token() {
+    let mut out = Vec::new();
+    write_token(&claims(), SECRET, &mut out).unwrap();
+    assert_eq!(out, create_token(&claims(), SECRET).unwrap().as_bytes());
+}
+
+#[test]
+fn writes_the_same_token_as_create_token_with_options() {
+    let algs = [Algorithm::Hs256, Algorithm::Hs384, Algorithm::Hs512];
+    let formats = [Format::MessagePack, Format::Json];
+    let encodings = [
+        Encoding::UrlSafeNoPad,
+        Encoding::UrlSafe,
+        Encoding::StandardNoPad,
+        Encoding::Standard,
+    ];
+    for alg in algs {
+        for format in formats {
+            for encoding in encodings {
+                for (named_fields, canonical) in [(true, false), (false, false), (true, true)] {
+                    let options = TokenOptions {
+                        alg,
+                        format,
+                        kid: Some("current".into()),
+                        typ: Some("access".into()),
+                        encoding,
+                        named_fields,
+                        canonical,
+                    };
+                    let created = create_token_with_options(&claims(), SECRET, &options).unwrap();
+                    assert_eq!(written(&options), created.as_str(), "{:?}", options);
+                }
+            }
+        }
+    }
+}